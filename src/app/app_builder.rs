@@ -1,40 +1,51 @@
 use crate::{millis, STEP_LED_COUNT};
 
-use crate::app::{App, State};
-use crate::clock::{Clock, ClockFactory, ClockTrait};
+use crate::app::App;
+use crate::board::{AdcInput, ArduinoUno, MicrosTimeSource};
+use crate::clock::{Clock, ClockFactory, ClockTrait, PollingEdgeSource, RatioClock};
 use crate::dac::Dac;
+use crate::hardware::Hardware;
+use crate::int0::Int0EdgeSource;
 use crate::led_controller::LedController;
 use crate::sequence_controller::SequenceController;
 use crate::serial_wrapper::SerialWrapper;
 use crate::trigger::TriggerFactory;
+use crate::turing_machine::TuringMachine;
+use crate::{EUCLID, RGB_LED_COUNT, SEQUENCES, SONG, SWITCH_MODE};
 use arduino::prelude::*;
 use arduino_uno as arduino;
 use arduino_uno::hal::port::{mode, Pin};
 use arduino_uno::{adc, spi};
+use void::ResultVoidExt;
+use ws2812_spi::prerendered::Ws2812;
+
+static mut OUTPUT_BUFFER: [u8; 136] = [0; 40 + (RGB_LED_COUNT * 12)];
 
 pub trait AppBuilderTrait {
+    type Hardware: Hardware;
     type Clock: ClockTrait;
 
     fn build(
         clock_factory: ClockFactory<Self::Clock>,
         trigger_factory: TriggerFactory,
-    ) -> App<Self::Clock>;
+    ) -> App<Self::Hardware, Self::Clock>;
 }
 
 pub struct AppBuilder {}
 
 impl AppBuilderTrait for AppBuilder {
-    type Clock = Clock;
+    type Hardware = ArduinoUno;
+    type Clock = RatioClock<Clock<<ArduinoUno as Hardware>::ClockInput>>;
 
     fn build(
         clock_factory: ClockFactory<Self::Clock>,
         trigger_factory: TriggerFactory,
-    ) -> App<Self::Clock> {
+    ) -> App<Self::Hardware, Self::Clock> {
         let dp = arduino::Peripherals::take().unwrap();
 
         let mut pins = arduino::Pins::new(dp.PORTB, dp.PORTC, dp.PORTD);
 
-        let sequence_change_output = pins.d4.into_output(&mut pins.ddr);
+        let sequence_change_output = pins.d4.into_output(&mut pins.ddr).downgrade();
         let step_output_pins: [Pin<mode::Output>; STEP_LED_COUNT] = [
             // pins.d2.into_output(&mut pins.ddr).downgrade(),
             // pins.d3.into_output(&mut pins.ddr).downgrade(),
@@ -57,22 +68,44 @@ impl AppBuilderTrait for AppBuilder {
         );
 
         let mut adc = adc::Adc::new(dp.ADC, Default::default());
-        let analog_input = Some(pins.a4.into_analog_input(&mut adc));
-
-        let a0 = pins.a0.into_output(&mut pins.ddr);
-        let a1 = pins.a1.into_output(&mut pins.ddr);
-        let a2 = pins.a2.into_output(&mut pins.ddr);
-        let a3 = pins.a3.into_output(&mut pins.ddr);
+        let mut a4 = pins.a4.into_analog_input(&mut adc);
+        // A reading is mostly the position of the pot (or the level of the floating reset input),
+        // only its lowest bit is noise. So the seed is made of bit 0 of 32 readings.
+        let seed = (0..32).fold(0u32, |seed, _| {
+            (seed << 1) | (nb::block!(adc.read(&mut a4)).void_unwrap() as u32 & 1)
+        });
+        // The Uno has no other free analog pin, so A4 is either connected to the pot (tempo or
+        // Turing machine probability) or used as reset input. `App::run()` reports which.
+        let (analog_input, reset_input) = if cfg!(feature = "reset_input") {
+            let reset_input = PollingEdgeSource::new(a4.into_floating_input(&mut pins.ddr));
+            (None, Some(reset_input))
+        } else {
+            (Some(AdcInput::new(adc, a4)), None)
+        };
+
+        let a0 = pins.a0.into_output(&mut pins.ddr).downgrade();
+        let a1 = pins.a1.into_output(&mut pins.ddr).downgrade();
+        let a2 = pins.a2.into_output(&mut pins.ddr).downgrade();
+        let a3 = pins.a3.into_output(&mut pins.ddr).downgrade();
         let dac = Dac::new(a0, a1, a2, a3);
 
-        let trigger_input = pins.d2.into_floating_input(&mut pins.ddr);
-        let clock_in = clock_factory.build(trigger_input);
+        let trigger_input =
+            Int0EdgeSource::new(dp.EXINT, pins.d2.into_floating_input(&mut pins.ddr));
+        let clock_in = clock_factory.with_seed(seed).build(trigger_input);
 
-        let trigger_out = pins.d3.into_output(&mut pins.ddr);
-        let trigger = trigger_factory.build(trigger_out);
+        let trigger_out = pins.d3.into_output(&mut pins.ddr).downgrade();
+        let trigger = trigger_factory.with_fallback_seed(seed).build(trigger_out);
 
         let sequence_change_input = pins.a5.into_pull_up_input(&mut pins.ddr);
-        let sequence_controller = SequenceController::new(sequence_change_input);
+        let mut sequence_controller = match (EUCLID, SONG) {
+            (Some(euclid), _) => SequenceController::with_euclid(sequence_change_input, euclid),
+            // `SONG` is checked at compile time
+            (None, Some(song)) => {
+                SequenceController::with_song(sequence_change_input, &SEQUENCES, song).unwrap()
+            }
+            (None, None) => SequenceController::new(sequence_change_input),
+        };
+        sequence_controller.set_switch_mode(SWITCH_MODE);
 
         let (spi, _) = spi::Spi::new(
             dp.SPI,
@@ -85,29 +118,32 @@ impl AppBuilderTrait for AppBuilder {
                 ..Default::default()
             },
         );
-        let led_controller = LedController::new(spi);
+        let outlet = unsafe { Ws2812::new(spi, &mut OUTPUT_BUFFER) };
+        let led_controller = LedController::new(outlet);
 
-        millis::millis_init(dp.TC0);
+        millis::millis_init(dp.TC0, millis::TIMER_CONFIG);
 
         // Enable interrupts globally
         unsafe { avr_device::interrupt::enable() };
 
-        App {
+        let mut app = App::new(
             step_output_pins,
+            dac,
             sequence_change_output,
-            adc,
-            analog_input,
             serial,
-            state: State {
-                trigger_interval: None,
-                auto_trigger_interval_countdown: 0,
-                last_trigger_time: None,
-            },
-            dac,
             trigger,
             clock_in,
-            led_controller,
+            reset_input,
+            // There is no spare pin for the run/stop gate, the transport is controlled via serial
+            None,
             sequence_controller,
+            led_controller,
+            analog_input,
+            MicrosTimeSource {},
+        );
+        if cfg!(feature = "turing_machine") {
+            app.set_turing_machine(Some(TuringMachine::new(seed)));
         }
+        app
     }
 }
@@ -1,51 +1,89 @@
+#[cfg(target_arch = "avr")]
 mod app_builder;
 
-use crate::clock::{Clock, ClockResult, ClockTrait, StepCounterType};
+use crate::clock::{ClockResult, ClockTrait, EdgeSource, StepCounterType};
 use crate::color::color_from_serial;
 use crate::dac::Dac;
 use crate::dac_byte::DacByte;
+use crate::hardware::{AnalogInput, Hardware, TimeSource};
 use crate::led_controller::LedController;
+use crate::playhead::Direction;
 use crate::sequence::Sequence;
-use crate::sequence_controller::{SequenceController, SequenceState};
+use crate::sequence_controller::{SequenceController, SequenceState, SwitchMode};
 use crate::serial_wrapper::SerialWrapper;
+use crate::tempo_pot::{interval_for_bpm, TempoPot};
+use crate::time::Instant;
+use crate::transport::{Transport, TransportCommand, TransportState};
 use crate::trigger::Trigger;
 use crate::trigger_state::TriggerState;
-use crate::{color, STEP_LED_COUNT};
+use crate::turing_machine::TuringMachine;
+use crate::{color, RESET_WINDOW, STEP_LED_COUNT};
+#[cfg(target_arch = "avr")]
 pub use app_builder::AppBuilder;
+#[cfg(target_arch = "avr")]
 pub use app_builder::AppBuilderTrait;
-use arduino::prelude::*;
-use arduino_uno as arduino;
-use arduino_uno::adc::Adc;
-use arduino_uno::hal::port::mode::{Analog, Floating, Output};
-use arduino_uno::hal::port::portc::PC4;
-use arduino_uno::hal::port::portd::PD4;
-use arduino_uno::hal::port::Pin;
 use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::serial::Read;
 use void::ResultVoidExt;
 
-#[derive(Clone)]
-struct State {
-    trigger_interval: Option<u32>,
-    auto_trigger_interval_countdown: u32,
-    last_trigger_time: Option<u32>,
-}
-
 #[allow(unused)]
-pub struct App<CLOCK: ClockTrait> {
-    step_output_pins: [Pin<Output>; STEP_LED_COUNT],
-    adc: Adc,
-    dac: Dac,
-    sequence_change_output: PD4<Output>,
-    serial: SerialWrapper<Floating>,
-    state: State,
-    trigger: Trigger,
+pub struct App<HW: Hardware, CLOCK: ClockTrait> {
+    step_output_pins: [HW::OutputPin; STEP_LED_COUNT],
+    dac: Dac<HW::OutputPin>,
+    sequence_change_output: HW::OutputPin,
+    serial: SerialWrapper<HW::Serial>,
+    trigger: Trigger<HW::OutputPin>,
     clock_in: CLOCK,
-    sequence_controller: SequenceController,
-    led_controller: LedController<'static>,
-    analog_input: Option<PC4<Analog>>,
+    reset_input: Option<HW::ResetInput>,
+    run_input: Option<HW::RunInput>,
+    sequence_controller: SequenceController<HW::ButtonInput>,
+    led_controller: LedController<HW::Leds>,
+    analog_input: Option<HW::AnalogInput>,
+    time_source: HW::TimeSource,
+    /// Time of the last clock-trigger
+    last_rise: Option<Instant>,
+    transport: Transport,
+    tempo_pot: TempoPot,
+    /// Mutates the sequences if the Turing machine mode is active
+    turing_machine: Option<TuringMachine>,
 }
 
-impl App<Clock> {
+impl<HW: Hardware, CLOCK: ClockTrait> App<HW, CLOCK> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        step_output_pins: [HW::OutputPin; STEP_LED_COUNT],
+        dac: Dac<HW::OutputPin>,
+        sequence_change_output: HW::OutputPin,
+        serial: SerialWrapper<HW::Serial>,
+        trigger: Trigger<HW::OutputPin>,
+        clock_in: CLOCK,
+        reset_input: Option<HW::ResetInput>,
+        run_input: Option<HW::RunInput>,
+        sequence_controller: SequenceController<HW::ButtonInput>,
+        led_controller: LedController<HW::Leds>,
+        analog_input: Option<HW::AnalogInput>,
+        time_source: HW::TimeSource,
+    ) -> Self {
+        App {
+            step_output_pins,
+            dac,
+            sequence_change_output,
+            serial,
+            trigger,
+            clock_in,
+            reset_input,
+            run_input,
+            sequence_controller,
+            led_controller,
+            analog_input,
+            time_source,
+            last_rise: None,
+            transport: Transport::new(),
+            tempo_pot: TempoPot::new(),
+            turing_machine: None,
+        }
+    }
+
     pub fn run(&mut self) -> ! {
         if cfg!(feature = "debug") {
             ufmt::uwriteln!(
@@ -60,6 +98,12 @@ impl App<Clock> {
             )
             .void_unwrap();
         }
+        if self.reset_input.is_some() {
+            ufmt::uwriteln!(&mut self.serial.get_serial(), "A4: reset input, pot off\r")
+                .void_unwrap();
+        } else if self.analog_input.is_some() {
+            ufmt::uwriteln!(&mut self.serial.get_serial(), "A4: pot\r").void_unwrap();
+        }
         let mut run_counter: u32 = 0;
 
         self.initialize_leds();
@@ -71,93 +115,201 @@ impl App<Clock> {
         }
     }
 
-    fn run_loop(&mut self, _run_counter: u32) {
-        if cfg!(feature = "test_adc") {
-            if let Some(a) = self.analog_input.as_mut() {
-                let adc_value: u16 = nb::block!(self.adc.read(&mut *a)).void_unwrap();
-                self.print_bits(adc_value as u8);
-                ufmt::uwriteln!(&mut self.serial, " adc_value:{}", adc_value).void_unwrap();
+    /// Activate or deactivate fill for the steps' trig conditions
+    pub fn set_fill(&mut self, fill: bool) {
+        self.trigger.set_fill(fill)
+    }
+
+    /// Override the direction of the sequences (`None` uses the direction of each sequence)
+    pub fn set_direction(&mut self, direction: Option<Direction>) {
+        self.clock_in.set_direction(direction)
+    }
+
+    /// Delay every second step by `swing` percent of the clock interval
+    pub fn set_swing(&mut self, swing: u8) {
+        self.clock_in.set_swing(swing)
+    }
+
+    /// Change when a sequence change from the button takes effect
+    pub fn set_switch_mode(&mut self, switch_mode: SwitchMode) {
+        self.sequence_controller.set_switch_mode(switch_mode)
+    }
+
+    /// Play the sequence at `pointer` from the start, like a button press with
+    /// `SwitchMode::Immediate`
+    ///
+    /// Returns `false` if there is no sequence at `pointer`.
+    pub fn select_sequence(&mut self, pointer: usize) -> bool {
+        if !self.sequence_controller.select_sequence(pointer) {
+            return false;
+        }
+        self.restart_sequence();
+        true
+    }
+
+    /// Activate (`Some`) or deactivate (`None`) the Turing machine mode
+    ///
+    /// While the mode is active the pot sets the probability of the mutations instead of the tempo.
+    pub fn set_turing_machine(&mut self, turing_machine: Option<TuringMachine>) {
+        self.turing_machine = turing_machine
+    }
+
+    /// Run, stop or pause the sequencer
+    pub fn set_transport(&mut self, command: TransportCommand, sequence: &Sequence) {
+        let state = match self.transport.apply(command) {
+            Some(state) => state,
+            None => return,
+        };
+        ufmt::uwriteln!(&mut self.serial, "transport {}\r", state).void_unwrap();
+
+        match state {
+            TransportState::Stopped => {
+                self.clock_in.restart();
+                self.last_rise = None;
+                self.trigger.silence();
+                self.set_all_step_pins_low();
             }
+            TransportState::Paused => self.trigger.silence(),
+            TransportState::Running => {}
         }
+        self.led_controller.show_transport(state, sequence);
+    }
 
-        let sequence_state = self.check_sequence_change();
+    pub fn run_loop(&mut self, _run_counter: u32) {
+        self.check_pot();
+
+        let now = self.time_source.now();
+        self.check_sequence_change(now);
+
+        // The only copy of the sequence per iteration, everything else borrows it
+        let sequence = self.current_sequence();
+        self.check_reset(now, &sequence);
+        self.check_transport(now, &sequence);
+        if !self.transport.is_running() {
+            return;
+        }
 
-        let sequence = sequence_state.sequence;
         let ClockResult {
             trigger_state,
-            step_counter,
-        } = self.clock_in.check(&mut self.serial, sequence);
-
-        // If `auto_trigger` is enabled
-        // if cfg!(feature = "auto_trigger") {
-        //     if let Some(trigger_interval) = self.state.trigger_interval {
-        //         self.state.auto_trigger_interval_countdown -= 1;
-        //         ufmt::uwriteln!(
-        //             &mut self.serial,
-        //             "tic:{} mod {} rc:{} ti:{}",
-        //             self.state.auto_trigger_interval_countdown,
-        //             run_counter % trigger_interval,
-        //             run_counter,
-        //             trigger_interval
-        //         )
-        //         .void_unwrap();
-        //         if self.state.auto_trigger_interval_countdown <= 0 {
-        //             // Reset the countdown
-        //             self.state.auto_trigger_interval_countdown = trigger_interval;
-        //             ufmt::uwrite!(&mut self.serial, "at").void_unwrap();
-        //
-        //             self.trigger_step(step_counter, sequence, led_controller);
-        //
-        //             // arduino::delay_ms(DELAY_TIME);
-        //             return;
-        //         }
-        //     }
-        // }
-
-        self.trigger.check_scheduled(
-            crate::millis::millis(),
-            trigger_state,
-            step_counter,
-            sequence,
-        );
-        self.trigger.check(trigger_state, step_counter, sequence);
+            mut step_counter,
+            wrapped,
+            timestamp,
+        } = self.clock_in.check(now, &mut self.serial, &sequence);
+        let mut sequence = sequence;
+        if trigger_state == TriggerState::Rise {
+            if let Some(new_sequence) = self.check_sequence_switch(wrapped) {
+                sequence = new_sequence;
+                step_counter = self.clock_in.start(&sequence);
+            }
+        }
+
+        self.trigger.set_clock_interval(self.clock_in.interval());
+        self.trigger
+            .check_scheduled(now, trigger_state, step_counter, &sequence);
+        self.trigger
+            .check(timestamp, trigger_state, step_counter, wrapped, &sequence);
         if trigger_state == TriggerState::Rise {
             ufmt::uwriteln!(&mut self.serial, "t ").void_unwrap();
+            self.last_rise = Some(timestamp);
 
-            // if cfg!(feature = "auto_trigger") {
-            //     if let Some(last_trigger_time) = self.state.last_trigger_time {
-            //         let i = run_counter - last_trigger_time;
-            //         self.state.trigger_interval = Some(i);
-            //         // Reset the countdown
-            //         self.state.auto_trigger_interval_countdown = i;
-            //         ufmt::uwrite!(
-            //             &mut self.serial,
-            //             " uti: {:#?}",
-            //             run_counter - last_trigger_time
-            //         )
-            //         .void_unwrap();
-            //     }
-            //     self.state.last_trigger_time = Some(run_counter);
-            // }
-
-            self.trigger_step(step_counter, sequence);
-        } else if cfg!(feature = "auto_trigger") {
-            ufmt::uwriteln!(&mut self.serial, "{} \r", step_counter).void_unwrap();
-
-            // self.trigger_step(step_counter, sequence, led_controller);
-            // arduino::delay_ms(2000);
+            self.trigger_step(step_counter, &sequence);
+            if let Some(turing_machine) = self.turing_machine.as_mut() {
+                turing_machine.shift(step_counter);
+            }
         }
         // arduino::delay_ms(DELAY_TIME);
     }
 
-    fn trigger_step(&mut self, step_counter: StepCounterType, sequence: Sequence) {
-        let step_pointer: u8 = 0b00000001 << step_counter;
+    /// Let the sequence controller apply a queued sequence change or advance the song, and
+    /// return the new sequence
+    ///
+    /// `wrapped` tells if the playhead wrapped around to the start of the loop with this
+    /// clock-trigger. The caller restarts the clock so that the new sequence starts with its first
+    /// step on this clock-trigger.
+    fn check_sequence_switch(&mut self, wrapped: bool) -> Option<Sequence> {
+        if !self.sequence_controller.finish_step(wrapped) {
+            return None;
+        }
+
+        let sequence = *self.sequence_controller.get_sequence();
+        self.show_sequence_change(&sequence);
+
+        Some(self.current_sequence())
+    }
+
+    /// Return the selected sequence (or the state of the Turing machine's register if the mode is
+    /// active)
+    fn current_sequence(&mut self) -> Sequence {
+        let sequence = self.sequence_controller.get_sequence();
+        match self.turing_machine.as_mut() {
+            Some(turing_machine) => *turing_machine.sequence(sequence),
+            None => *sequence,
+        }
+    }
+
+    /// Set the tempo of the internal clock (or the probability of the Turing machine) from the pot
+    /// on the analog input
+    fn check_pot(&mut self) {
+        let reading = match self.analog_input.as_mut() {
+            Some(analog_input) => analog_input.read(),
+            None => return,
+        };
+        if let Some(turing_machine) = self.turing_machine.as_mut() {
+            if let Some(probability) = turing_machine.update_pot(reading) {
+                ufmt::uwriteln!(&mut self.serial, "probability {}%\r", probability).void_unwrap();
+            }
+        } else if let Some(bpm) = self.tempo_pot.update(reading) {
+            ufmt::uwriteln!(&mut self.serial, "tempo {} bpm\r", bpm).void_unwrap();
+            self.clock_in.set_internal_interval(interval_for_bpm(bpm));
+        }
+    }
+
+    /// Apply transport commands from the run/stop gate and the serial input
+    fn check_transport(&mut self, now: Instant, sequence: &Sequence) {
+        while let Some(edge) = self.run_input.as_mut().and_then(|i| i.next_edge(now)) {
+            let command = if edge.rising {
+                TransportCommand::Run
+            } else {
+                TransportCommand::Stop
+            };
+            self.set_transport(command, sequence);
+        }
+        while let Ok(byte) = self.serial.get_serial().read() {
+            if let Some(command) = TransportCommand::from_byte(byte) {
+                self.set_transport(command, sequence);
+            }
+        }
+    }
+
+    /// Restart the sequence on a rising edge of the reset input
+    ///
+    /// The reset is handled before the clock, so a clock-trigger at the same time plays the
+    /// first step. If the clock-trigger was handled just before the reset, its step is replaced
+    /// by the first one.
+    fn check_reset(&mut self, now: Instant, sequence: &Sequence) {
+        while let Some(edge) = self.reset_input.as_mut().and_then(|i| i.next_edge(now)) {
+            if !edge.rising {
+                continue;
+            }
+            ufmt::uwriteln!(&mut self.serial, "reset\r").void_unwrap();
+
+            match self.last_rise.take() {
+                Some(last_rise) if edge.timestamp.duration_since(last_rise) <= RESET_WINDOW => {
+                    let step_counter = self.clock_in.start(sequence);
+                    self.trigger_step(step_counter, sequence);
+                }
+                _ => self.clock_in.restart(),
+            }
+        }
+    }
+
+    fn trigger_step(&mut self, step_counter: StepCounterType, sequence: &Sequence) {
         self.set_dac(sequence, step_counter);
 
         self.set_all_step_pins_low();
 
         let output_pin = self.step_output_pins.get_mut(step_counter);
-        let sequence_matches = sequence.matches(step_pointer);
+        let sequence_matches = sequence.matches(step_counter);
 
         if let Some(pin) = output_pin {
             if sequence_matches {
@@ -166,15 +318,19 @@ impl App<Clock> {
                 pin.set_low().void_unwrap();
             };
         }
+        let pending_sequence = self.sequence_controller.pending_sequence();
         self.led_controller
-            .show_step(sequence, step_counter, sequence_matches)
+            .show_step(
+                sequence,
+                step_counter,
+                sequence_matches,
+                pending_sequence.as_ref(),
+            )
             .unwrap();
     }
 
-    fn set_dac(&mut self, sequence: Sequence, step_counter: StepCounterType) {
-        let step_pointer: u8 = 0b00000001 << step_counter;
-
-        match sequence.get_step(step_pointer) {
+    fn set_dac(&mut self, sequence: &Sequence, step_counter: StepCounterType) {
+        match sequence.get_step(step_counter) {
             None => self.dac.set(DacByte::new(0)),
             Some(step) => self.dac.set(step),
         }
@@ -187,37 +343,53 @@ impl App<Clock> {
     }
 
     #[allow(unused)]
-    fn set_pins_low(&self, output_pins: &mut [Pin<Output>]) {
+    fn set_pins_low(&self, output_pins: &mut [HW::OutputPin]) {
         for output_pin in output_pins {
             output_pin.set_low().void_unwrap();
         }
     }
 
-    fn check_sequence_change(&mut self) -> SequenceState {
-        let sequence_state = self.sequence_controller.check_sequence_change();
+    fn check_sequence_change(&mut self, now: Instant) -> SequenceState {
+        let sequence_state = self.sequence_controller.check_sequence_change(now);
+        if let Some(interval) = sequence_state.tap_interval {
+            ufmt::uwriteln!(&mut self.serial, "tap {} ms\r", interval.as_millis()).void_unwrap();
+            self.clock_in.set_internal_interval(interval);
+        }
         if sequence_state.did_change {
-            ufmt::uwriteln!(
-                &mut self.serial,
-                "change sequence {}\r",
-                sequence_state.sequence
-            )
-            .void_unwrap();
+            self.restart_sequence();
+        }
+        if sequence_state.did_queue {
+            if let Some(pending_sequence) = self.sequence_controller.pending_sequence() {
+                self.led_controller.show_pending(&pending_sequence);
+            }
+        }
 
-            self.clock_in.reset();
+        sequence_state
+    }
 
-            self.sequence_change_output.set_high().void_unwrap();
-            self.set_step_output_pins_for_sequence(sequence_state.sequence);
+    /// Play the sequence selected in the sequence controller from the start
+    fn restart_sequence(&mut self) {
+        self.clock_in.reset();
+        self.last_rise = None;
 
-            self.led_controller.show_sequence(sequence_state.sequence);
-        }
+        let sequence = *self.sequence_controller.get_sequence();
+        self.show_sequence_change(&sequence);
+        self.set_step_output_pins_for_sequence(&sequence);
+    }
 
-        sequence_state
+    fn show_sequence_change(&mut self, sequence: &Sequence) {
+        ufmt::uwriteln!(&mut self.serial, "change sequence {}\r", sequence).void_unwrap();
+
+        self.sequence_change_output.set_high().void_unwrap();
+        self.led_controller.show_sequence(sequence);
+        if let Some(turing_machine) = self.turing_machine.as_mut() {
+            turing_machine.load(sequence);
+        }
     }
 
-    fn set_step_output_pins_for_sequence(&mut self, sequence: Sequence) {
+    fn set_step_output_pins_for_sequence(&mut self, sequence: &Sequence) {
         for (i, step_output_pin) in self.step_output_pins.iter_mut().enumerate() {
-            let current_bit = 0b00000001 << i;
-            if sequence.matches(current_bit) {
+            if sequence.matches(i) {
                 step_output_pin.set_high().void_unwrap();
             } else {
                 step_output_pin.set_low().void_unwrap();
@@ -225,7 +397,7 @@ impl App<Clock> {
         }
     }
 
-    fn initialize_leds(&mut self) {
+    pub fn initialize_leds(&mut self) {
         let blank = Default::default();
         let initial_colors = color::get_initial_colors();
         // Workaround for the bright green led color on startup
@@ -260,3 +432,370 @@ impl App<Clock> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::clock::ClockFactory;
+    use crate::color::{color_for_dac_byte, BRIGHTNESS_DEFAULT};
+    use crate::dac_byte::DacByte;
+    use crate::mock::{MockAnalogInput, MockPeripherals};
+    use crate::playhead::Direction;
+    use crate::sequence_controller::{SequenceController, SongEntry, SwitchMode};
+    use crate::trigger::TriggerFactory;
+    use crate::SEQUENCES;
+
+    #[test]
+    fn internal_clock_triggers_step() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        app.run_loop(1);
+        assert!(!peripherals.trigger_output.is_high());
+
+        peripherals.time_source.set(250);
+        app.run_loop(2);
+        assert!(peripherals.trigger_output.is_high());
+        assert!(peripherals.step_output_pins[1].is_high());
+        assert_eq!(peripherals.dac_value(), 3);
+
+        peripherals.time_source.set(255);
+        app.run_loop(3);
+        assert!(!peripherals.trigger_output.is_high());
+    }
+
+    #[test]
+    fn sequence_change_button_switches_sequence() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        peripherals.sequence_change_input.set(false);
+        app.run_loop(1);
+        assert!(!peripherals.sequence_change_output.is_high());
+        // The sequence changes when the button is released
+        peripherals.sequence_change_input.set(true);
+        app.run_loop(1);
+        assert!(peripherals.sequence_change_output.is_high());
+
+        // Second sequence: `seq!(15, 5, 5, 5, 0)`
+        peripherals.sequence_change_input.set(true);
+        peripherals.time_source.set(250);
+        app.run_loop(2);
+        assert_eq!(peripherals.dac_value(), 5);
+    }
+
+    #[test]
+    fn button_bounces_are_ignored() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        // The press bounces for 6 ms
+        for (millis, pressed) in [(100, true), (102, false), (104, true), (106, false)].iter() {
+            peripherals.time_source.set(*millis);
+            peripherals.sequence_change_input.set(!pressed);
+            app.run_loop(1);
+        }
+        assert_eq!(app.sequence_controller.get_sequence().len(), 5);
+
+        // A press after `DEBOUNCE_TIME` counts
+        peripherals.time_source.set(126);
+        peripherals.sequence_change_input.set(false);
+        app.run_loop(1);
+        peripherals.time_source.set(200);
+        peripherals.sequence_change_input.set(true);
+        app.run_loop(1);
+        assert_eq!(app.sequence_controller.get_sequence().len(), 8);
+    }
+
+    #[test]
+    fn select_sequence_ignores_the_switch_mode() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+        app.set_switch_mode(SwitchMode::EndOfLoop);
+
+        assert!(app.select_sequence(1));
+        assert!(peripherals.sequence_change_output.is_high());
+        assert_eq!(app.sequence_controller.get_sequence().len(), 5);
+        assert!(!app.select_sequence(SEQUENCES.len()));
+        assert_eq!(app.sequence_controller.get_sequence().len(), 5);
+    }
+
+    #[test]
+    fn tapping_the_button_sets_tempo() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        // A long press from 1000 to 2100 ms, then taps at 120 BPM (125 ms per step)
+        let pressed = |millis: u32| matches!(millis, 1000..=2099 | 2500 | 3000 | 3500);
+        let mut dac_values = Vec::new();
+        let mut rises_after_taps = 0;
+        let mut was_high = peripherals.trigger_output.is_high();
+        for millis in 1..=4500 {
+            peripherals.time_source.set(millis);
+            peripherals.sequence_change_input.set(!pressed(millis));
+            app.run_loop(millis);
+            let is_high = peripherals.trigger_output.is_high();
+            if is_high && !was_high {
+                dac_values.push(peripherals.dac_value());
+                if millis > 3500 {
+                    rises_after_taps += 1;
+                }
+            }
+            was_high = is_high;
+        }
+        assert_eq!(rises_after_taps, 8);
+
+        // Neither the long press nor the taps change or restart the sequence
+        assert!(!peripherals.sequence_change_output.is_high());
+        assert_eq!(app.sequence_controller.get_sequence().len(), 8);
+        let steps = [1, 3, 5, 8, 9, 10, 12, 15];
+        let expected: Vec<u8> = steps
+            .iter()
+            .cycle()
+            .skip(1)
+            .take(dac_values.len())
+            .copied()
+            .collect();
+        assert_eq!(dac_values, expected);
+    }
+
+    #[test]
+    fn presses_at_a_tap_pace_step_through_the_sequences() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        for millis in [1000, 1500, 2000].iter() {
+            peripherals.time_source.set(*millis);
+            peripherals.sequence_change_input.set(false);
+            app.run_loop(1);
+            peripherals.sequence_change_input.set(true);
+            app.run_loop(1);
+        }
+        // Fourth sequence: `seq!(15, 15, 15, 15, 15, 15, 15)`
+        assert_eq!(app.sequence_controller.get_sequence().len(), 7);
+    }
+
+    #[test]
+    fn reset_input_restarts_sequence() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        // First sequence: `seq!(1, 3, 5, 8, 9, 10, 12, 15)`
+        for millis in [250, 500, 750].iter() {
+            peripherals.time_source.set(*millis);
+            app.run_loop(1);
+        }
+        assert_eq!(peripherals.dac_value(), 8);
+
+        // Reset before the clock-trigger: the clock-trigger plays the first step
+        peripherals.reset_input.set(true);
+        peripherals.time_source.set(1000);
+        app.run_loop(2);
+        assert_eq!(peripherals.dac_value(), 1);
+        assert!(peripherals.step_output_pins[0].is_high());
+
+        // Reset shortly after the clock-trigger: the step is replaced by the first one
+        peripherals.reset_input.set(false);
+        peripherals.time_source.set(1250);
+        app.run_loop(3);
+        assert_eq!(peripherals.dac_value(), 3);
+        peripherals.reset_input.set(true);
+        peripherals.time_source.set(1252);
+        app.run_loop(4);
+        assert_eq!(peripherals.dac_value(), 1);
+
+        peripherals.time_source.set(1500);
+        app.run_loop(5);
+        assert_eq!(peripherals.dac_value(), 3);
+    }
+
+    #[test]
+    fn transport_stops_and_pauses_sequence() {
+        let peripherals = MockPeripherals::new();
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        peripherals.time_source.set(250);
+        app.run_loop(1);
+        assert_eq!(peripherals.dac_value(), 3);
+
+        // Paused: the clock is ignored
+        peripherals.serial.push_input(b"p");
+        peripherals.time_source.set(500);
+        app.run_loop(2);
+        assert!(!peripherals.trigger_output.is_high());
+        assert_eq!(peripherals.dac_value(), 3);
+
+        // Continue with the next step
+        peripherals.serial.push_input(b"p");
+        peripherals.time_source.set(750);
+        app.run_loop(3);
+        assert_eq!(peripherals.dac_value(), 5);
+
+        // Stopped by the gate: the sequence starts over when running again
+        peripherals.run_input.set(false);
+        peripherals.time_source.set(1000);
+        app.run_loop(4);
+        assert!(!peripherals.trigger_output.is_high());
+        assert!(!peripherals.step_output_pins[2].is_high());
+        peripherals.run_input.set(true);
+        peripherals.time_source.set(1250);
+        app.run_loop(5);
+        assert_eq!(peripherals.dac_value(), 1);
+        assert!(peripherals.trigger_output.is_high());
+    }
+
+    #[test]
+    fn tempo_pot_sets_internal_clock_interval() {
+        let peripherals = MockPeripherals {
+            analog_input: Some(MockAnalogInput::new()),
+            ..MockPeripherals::new()
+        };
+        let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+
+        // Lowest position: 40 BPM (375 ms per step)
+        app.run_loop(1);
+        peripherals.time_source.set(250);
+        app.run_loop(2);
+        assert!(!peripherals.trigger_output.is_high());
+        peripherals.time_source.set(375);
+        app.run_loop(3);
+        assert!(peripherals.trigger_output.is_high());
+    }
+
+    #[test]
+    fn song_advances_at_the_end_of_the_loop() {
+        static SONG: [SongEntry; 2] = [SongEntry::new(1, 2), SongEntry::new(0, 1)];
+        let peripherals = MockPeripherals::new();
+        let sequence_controller = SequenceController::with_song(
+            peripherals.sequence_change_input.clone(),
+            &SEQUENCES,
+            &SONG,
+        )
+        .unwrap();
+        let mut app = peripherals.build_app_with_sequence_controller(
+            ClockFactory::new(),
+            TriggerFactory::new(),
+            sequence_controller,
+        );
+
+        let dac_values: Vec<u8> = (1..=16)
+            .map(|step| {
+                peripherals.time_source.set(step * 250);
+                app.run_loop(step);
+                peripherals.dac_value()
+            })
+            .collect();
+        // Twice `seq!(15, 5, 5, 5, 0)` (starting with the second step), then the first sequence
+        assert_eq!(
+            dac_values,
+            [5, 5, 5, 0, 15, 5, 5, 5, 0, 1, 3, 5, 8, 9, 10, 12]
+        );
+    }
+
+    #[test]
+    fn song_advances_when_the_playhead_wraps_in_any_direction() {
+        static SONG: [SongEntry; 2] = [SongEntry::new(1, 1), SongEntry::new(0, 1)];
+        let lengths = |direction: Direction, steps: u32| {
+            let peripherals = MockPeripherals::new();
+            let sequence_controller = SequenceController::with_song(
+                peripherals.sequence_change_input.clone(),
+                &SEQUENCES,
+                &SONG,
+            )
+            .unwrap();
+            let mut app = peripherals.build_app_with_sequence_controller(
+                ClockFactory::new(),
+                TriggerFactory::new(),
+                sequence_controller,
+            );
+            app.set_direction(Some(direction));
+
+            (1..=steps)
+                .map(|step| {
+                    peripherals.time_source.set(step * 250);
+                    app.run_loop(step);
+                    app.sequence_controller.get_sequence().len()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // One loop of `seq!(15, 5, 5, 5, 0)` (starting with the second step), then the first
+        // sequence
+        let once = [vec![5; 4], vec![8; 8], vec![5]].concat();
+        assert_eq!(lengths(Direction::Forward, 13), once);
+        assert_eq!(lengths(Direction::Reverse, 13), once);
+        assert_eq!(lengths(Direction::Brownian, 13), once);
+        // A ping-pong loop visits the inner steps twice
+        assert_eq!(
+            lengths(Direction::PingPong, 23),
+            [vec![5; 7], vec![8; 14], vec![5; 2]].concat()
+        );
+    }
+
+    #[test]
+    fn end_of_loop_switch_waits_for_the_playhead_to_wrap() {
+        let lengths = |direction: Direction, steps: u32| {
+            let peripherals = MockPeripherals::new();
+            let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+            app.set_switch_mode(SwitchMode::EndOfLoop);
+            app.set_direction(Some(direction));
+
+            peripherals.time_source.set(100);
+            peripherals.sequence_change_input.set(false);
+            app.run_loop(1);
+            peripherals.sequence_change_input.set(true);
+            app.run_loop(1);
+            (1..=steps)
+                .map(|step| {
+                    peripherals.time_source.set(step * 250);
+                    app.run_loop(step);
+                    app.sequence_controller.get_sequence().len()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lengths(Direction::Reverse, 9), [8, 8, 8, 8, 8, 8, 8, 5, 5]);
+        assert_eq!(
+            lengths(Direction::PingPong, 15),
+            [vec![8; 13], vec![5; 2]].concat()
+        );
+        assert_eq!(
+            lengths(Direction::PingPongRepeat, 17),
+            [vec![8; 15], vec![5; 2]].concat()
+        );
+    }
+
+    #[test]
+    fn switch_mode_delays_sequence_change() {
+        let play = |switch_mode: SwitchMode, steps: u32| {
+            let peripherals = MockPeripherals::new();
+            let mut app = peripherals.build_app(ClockFactory::new(), TriggerFactory::new());
+            app.set_switch_mode(switch_mode);
+
+            peripherals.time_source.set(100);
+            peripherals.sequence_change_input.set(false);
+            app.run_loop(1);
+            peripherals.sequence_change_input.set(true);
+            app.run_loop(1);
+            let dac_values: Vec<u8> = (1..=steps)
+                .map(|step| {
+                    peripherals.time_source.set(step * 250);
+                    app.run_loop(step);
+                    if step == 2 {
+                        // The LEDs show the pending `seq!(15, 5, 5, 5, 0)` instead of the `9`
+                        let color = color_for_dac_byte(DacByte::new(0), 255, BRIGHTNESS_DEFAULT);
+                        assert_eq!(peripherals.leds.data()[4], color);
+                    }
+                    peripherals.dac_value()
+                })
+                .collect();
+            dac_values
+        };
+
+        assert_eq!(play(SwitchMode::NextStep, 2), [15, 5]);
+        // The first sequence is finished before `seq!(15, 5, 5, 5, 0)` starts
+        assert_eq!(
+            play(SwitchMode::EndOfLoop, 9),
+            [3, 5, 8, 9, 10, 12, 15, 15, 5]
+        );
+    }
+}
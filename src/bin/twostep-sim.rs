@@ -0,0 +1,271 @@
+//! Run the sequencer on the host and print a timeline of its outputs
+//!
+//! Usage: twostep-sim [--duration MS] [--tick MS] [--sequence INDEX]
+//!                    [--trigger-mode follow|hold|pulse] [--external-clock INTERVAL_MS]
+//!                    [--external-clock-stop MS] [--clock-mode internal|external|mixed|follow]
+//!                    [--clock-ratio /N|xN] [--reset MS] [--stop MS] [--start MS]
+//!                    [--swing PERCENT] [--external-swing]
+//!                    [--direction forward|reverse|pingpong|pingpong-repeat|random|brownian]
+//!                    [--euclid STEPS:PULSES[:ROTATION]] [--turing PERCENT]
+//!                    [--song SEQUENCE:REPEATS,...] [--switch-mode immediate|next-step|end-of-loop]
+//!                    [--press MS] [--all] [--vcd FILE]
+//!
+//! Without `--clock-mode` the internal clock is used, or the external clock if
+//! `--external-clock` is given. `--reset` sends a 10 ms pulse to the reset input.
+//! `--stop` and `--start` drive the run/stop gate input. `--external-swing` also swings the
+//! steps of the external clock. With `--euclid` a Euclidean rhythm is played instead of the
+//! sequences and `--sequence` adds pulses. `--turing` mutates the steps of the sequence with the
+//! given probability. `--song` plays the sequences in the given order and `--sequence` starts
+//! at the given entry. `--press` presses the sequence-change button and `--switch-mode` delays
+//! the sequence change of the press.
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process;
+use twostep::clock::{ClockFactory, ClockMode, ClockRatio};
+use twostep::euclid::Euclid;
+use twostep::playhead::Direction;
+use twostep::sequence_controller::{is_valid_song, SongEntry, SwitchMode};
+use twostep::simulator::{ClockSignal, Simulator, Snapshot};
+use twostep::trigger::{TriggerFactory, TriggerMode};
+use twostep::vcd::VcdRecorder;
+use twostep::SEQUENCES;
+
+struct Options {
+    duration: u32,
+    tick: u32,
+    sequence: usize,
+    trigger_mode: TriggerMode,
+    external_clock: Option<u32>,
+    external_clock_stop: Option<u32>,
+    clock_mode: Option<ClockMode>,
+    clock_ratio: ClockRatio,
+    reset: Option<u32>,
+    stop: Option<u32>,
+    start: Option<u32>,
+    swing: u8,
+    external_swing: bool,
+    direction: Option<Direction>,
+    euclid: Option<Euclid>,
+    turing: Option<u8>,
+    song: Option<Vec<SongEntry>>,
+    switch_mode: SwitchMode,
+    press: Option<u32>,
+    print_all: bool,
+    vcd: Option<String>,
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let clock_factory = match options.clock_mode {
+        Some(clock_mode) => ClockFactory::with_clock_mode(clock_mode),
+        None => ClockFactory::with_internal_clock(options.external_clock.is_none()),
+    }
+    .with_clock_ratio(options.clock_ratio)
+    .with_swing(options.swing)
+    .with_external_swing(options.external_swing);
+    let trigger_factory = TriggerFactory::with_trigger_mode(options.trigger_mode);
+    let mut simulator = match (options.euclid, options.song) {
+        (Some(euclid), _) => Simulator::with_euclid(clock_factory, trigger_factory, euclid),
+        (None, Some(song)) => Simulator::with_song(
+            clock_factory,
+            trigger_factory,
+            Box::leak(song.into_boxed_slice()),
+        )
+        .expect("the song is checked by parse_options"),
+        (None, None) => Simulator::new(clock_factory, trigger_factory),
+    };
+    simulator.set_switch_mode(options.switch_mode);
+    simulator.set_direction(options.direction);
+    simulator.set_turing_machine(options.turing);
+    let sequence_pointer = match options.euclid {
+        Some(euclid) => {
+            (euclid.pulses() as usize + options.sequence) % (euclid.steps() as usize + 1)
+        }
+        None => options.sequence,
+    };
+    simulator.select_sequence(sequence_pointer);
+    simulator.set_clock_signal(options.external_clock.map(|interval| ClockSignal {
+        interval,
+        pulse_width: interval / 2,
+    }));
+
+    let mut vcd_recorder = options.vcd.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|e| exit_with_error(path, e));
+        VcdRecorder::new(BufWriter::new(file)).unwrap_or_else(|e| exit_with_error(path, e))
+    });
+
+    println!("     time  clk  gate  steps  seq  cv  leds");
+    let mut last_snapshot: Option<Snapshot> = None;
+    let mut millis = 0;
+    while millis <= options.duration {
+        if Some(millis) == options.external_clock_stop {
+            simulator.set_clock_signal(None);
+        }
+        if let Some(reset) = options.reset {
+            simulator.set_reset_input(millis >= reset && millis < reset + 10);
+        }
+        if Some(millis) == options.stop {
+            simulator.set_run_input(false);
+        }
+        if Some(millis) == options.start {
+            simulator.set_run_input(true);
+        }
+        if Some(millis) == options.press {
+            simulator.press_sequence_change_button();
+        }
+        let snapshot = simulator.tick(millis);
+        if options.print_all || !is_same_output(last_snapshot.as_ref(), &snapshot) {
+            print_snapshot(&snapshot);
+        }
+        if let Some(recorder) = vcd_recorder.as_mut() {
+            if let Err(e) = recorder.record(&snapshot) {
+                exit_with_error(options.vcd.as_ref().unwrap(), e);
+            }
+        }
+        last_snapshot = Some(snapshot);
+        millis += options.tick;
+    }
+
+    if let Some(recorder) = vcd_recorder {
+        if let Err(e) = recorder.into_inner() {
+            exit_with_error(options.vcd.as_ref().unwrap(), e);
+        }
+    }
+}
+
+fn exit_with_error(path: &str, error: std::io::Error) -> ! {
+    eprintln!("Could not write {}: {}", path, error);
+    process::exit(1);
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        duration: 2000,
+        tick: 1,
+        sequence: 0,
+        trigger_mode: TriggerMode::Pulse,
+        external_clock: None,
+        external_clock_stop: None,
+        clock_mode: None,
+        clock_ratio: ClockRatio::default(),
+        reset: None,
+        stop: None,
+        start: None,
+        swing: 0,
+        external_swing: false,
+        direction: None,
+        euclid: None,
+        turing: None,
+        song: None,
+        switch_mode: SwitchMode::Immediate,
+        press: None,
+        print_all: false,
+        vcd: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--duration" => options.duration = parse_value(&arg, args.next())?,
+            "--tick" => options.tick = parse_value(&arg, args.next())?,
+            "--sequence" => options.sequence = parse_value(&arg, args.next())?,
+            "--external-clock" => options.external_clock = Some(parse_value(&arg, args.next())?),
+            "--trigger-mode" => options.trigger_mode = parse_value(&arg, args.next())?,
+            "--external-clock-stop" => {
+                options.external_clock_stop = Some(parse_value(&arg, args.next())?)
+            }
+            "--clock-mode" => options.clock_mode = Some(parse_value(&arg, args.next())?),
+            "--clock-ratio" => options.clock_ratio = parse_value(&arg, args.next())?,
+            "--reset" => options.reset = Some(parse_value(&arg, args.next())?),
+            "--stop" => options.stop = Some(parse_value(&arg, args.next())?),
+            "--start" => options.start = Some(parse_value(&arg, args.next())?),
+            "--swing" => options.swing = parse_value(&arg, args.next())?,
+            "--external-swing" => options.external_swing = true,
+            "--direction" => options.direction = Some(parse_value(&arg, args.next())?),
+            "--euclid" => options.euclid = Some(parse_value(&arg, args.next())?),
+            "--turing" => options.turing = Some(parse_value(&arg, args.next())?),
+            "--song" => options.song = Some(parse_list(&arg, args.next())?),
+            "--switch-mode" => options.switch_mode = parse_value(&arg, args.next())?,
+            "--press" => options.press = Some(parse_value(&arg, args.next())?),
+            "--all" => options.print_all = true,
+            "--vcd" => options.vcd = Some(parse_value(&arg, args.next())?),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+
+    if options.tick == 0 {
+        return Err("--tick must be greater than 0".to_owned());
+    }
+    if options.euclid.is_none() && options.sequence >= SEQUENCES.len() {
+        return Err(format!("--sequence must be lower than {}", SEQUENCES.len()));
+    }
+    if let Some(song) = &options.song {
+        if !is_valid_song(song, SEQUENCES.len()) {
+            return Err(format!(
+                "--song sequences must be lower than {}",
+                SEQUENCES.len()
+            ));
+        }
+        if options.euclid.is_none() && options.sequence >= song.len() {
+            return Err(format!("--sequence must be lower than {}", song.len()));
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    value
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("Missing or invalid value for {}", name))
+}
+
+fn parse_list<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<Vec<T>, String> {
+    value
+        .and_then(|v| v.split(',').map(|item| item.parse().ok()).collect())
+        .ok_or_else(|| format!("Missing or invalid value for {}", name))
+}
+
+/// Compare everything but the timestamp
+fn is_same_output(last: Option<&Snapshot>, current: &Snapshot) -> bool {
+    match last {
+        Some(last) => {
+            Snapshot {
+                millis: current.millis,
+                ..*last
+            } == *current
+        }
+        None => false,
+    }
+}
+
+fn print_snapshot(snapshot: &Snapshot) {
+    let steps: String = snapshot
+        .step_outputs
+        .iter()
+        .map(|high| if *high { '1' } else { '0' })
+        .collect();
+    let leds: Vec<String> = snapshot
+        .leds
+        .iter()
+        .map(|c| format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b))
+        .collect();
+
+    println!(
+        "{:>7}ms  {:>3}  {:>4}  {}  {:>3}  {:>2}  {}",
+        snapshot.millis,
+        snapshot.clock_input as u8,
+        snapshot.trigger_output as u8,
+        steps,
+        snapshot.sequence_change_output as u8,
+        snapshot.dac,
+        leds.join(" ")
+    );
+}
@@ -0,0 +1,152 @@
+//! Render the CV (left channel) and trigger (right channel) output of sequences to a WAV file
+//!
+//! Usage: twostep-wav [--tempo BPM] [--loops N] [--trigger-mode follow|hold|pulse]
+//!                    [--sequence INDEX | --all | --steps VALUE,VALUE,...]
+//!                    [--sample-rate HZ] OUTPUT_FILE
+//!
+//! The sequencer is driven by an external clock with one step per beat.
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process;
+use twostep::clock::ClockFactory;
+use twostep::dac_byte::DacByte;
+use twostep::sequence::{Sequence, MAX_STEPS};
+use twostep::simulator::{ClockSignal, Simulator};
+use twostep::trigger::{TriggerFactory, TriggerMode};
+use twostep::wav::WavRenderer;
+use twostep::SEQUENCES;
+
+/// Highest tempo at which the clock signal still has a high and a low phase of at least 1 ms
+const MAX_TEMPO: u32 = 30_000;
+
+struct Options {
+    tempo: u32,
+    loops: usize,
+    trigger_mode: TriggerMode,
+    sequence: usize,
+    all: bool,
+    steps: Option<Sequence>,
+    sample_rate: u32,
+    output: Option<String>,
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let sequences: &'static [Sequence] = match options.steps {
+        Some(sequence) => Box::leak(Box::new([sequence])),
+        None => &SEQUENCES,
+    };
+    let sequence_indexes: Vec<usize> = if options.all {
+        (0..sequences.len()).collect()
+    } else if options.steps.is_some() {
+        vec![0]
+    } else {
+        vec![options.sequence]
+    };
+
+    let interval = 60_000 / options.tempo;
+    let mut simulator = Simulator::with_sequences(
+        ClockFactory::with_internal_clock(false),
+        TriggerFactory::with_trigger_mode(options.trigger_mode),
+        sequences,
+    );
+    simulator.set_clock_signal(Some(ClockSignal {
+        interval,
+        pulse_width: interval / 2,
+    }));
+
+    let mut renderer = WavRenderer::new(options.sample_rate);
+    let mut millis = 0;
+    for index in sequence_indexes {
+        simulator.select_sequence(index);
+
+        let end = millis + (sequences[index].len() * options.loops) as u32 * interval;
+        while millis < end {
+            renderer.record(&simulator.tick(millis));
+            millis += 1;
+        }
+    }
+    renderer.record(&simulator.tick(millis));
+
+    let path = options.output.unwrap();
+    let result = File::create(&path).and_then(|file| renderer.write(BufWriter::new(file)));
+    if let Err(error) = result {
+        eprintln!("Could not write {}: {}", path, error);
+        process::exit(1);
+    }
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        tempo: 120,
+        loops: 2,
+        trigger_mode: TriggerMode::Pulse,
+        sequence: 0,
+        all: false,
+        steps: None,
+        sample_rate: 48_000,
+        output: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tempo" => options.tempo = parse_value(&arg, args.next())?,
+            "--loops" => options.loops = parse_value(&arg, args.next())?,
+            "--trigger-mode" => options.trigger_mode = parse_value(&arg, args.next())?,
+            "--sequence" => options.sequence = parse_value(&arg, args.next())?,
+            "--all" => options.all = true,
+            "--steps" => {
+                let steps: String = parse_value(&arg, args.next())?;
+                options.steps = Some(parse_sequence(&steps)?);
+            }
+            "--sample-rate" => options.sample_rate = parse_value(&arg, args.next())?,
+            _ if !arg.starts_with("--") && options.output.is_none() => options.output = Some(arg),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+
+    if options.output.is_none() {
+        return Err("Missing output file".to_owned());
+    }
+    if options.tempo == 0 || options.tempo > MAX_TEMPO {
+        return Err(format!("--tempo must be between 1 and {}", MAX_TEMPO));
+    }
+    if options.sample_rate == 0 {
+        return Err("--sample-rate must be greater than 0".to_owned());
+    }
+    if options.sequence >= SEQUENCES.len() {
+        return Err(format!("--sequence must be lower than {}", SEQUENCES.len()));
+    }
+
+    Ok(options)
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+    value
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("Missing or invalid value for {}", name))
+}
+
+/// Build a sequence from a comma separated list of DAC values (e.g. `15,0,7,0`)
+fn parse_sequence(input: &str) -> Result<Sequence, String> {
+    let mut steps = vec![];
+    for value in input.split(',').map(str::trim) {
+        match value.parse::<u8>() {
+            Ok(v) if v <= DacByte::max().value() => steps.push(DacByte::new(v)),
+            _ => return Err(format!("Invalid step value {}", value)),
+        }
+    }
+    if steps.len() > MAX_STEPS {
+        return Err(format!("--steps supports at most {} steps", MAX_STEPS));
+    }
+
+    Ok(Sequence::new(&steps))
+}
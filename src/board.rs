@@ -0,0 +1,61 @@
+use crate::clock::PollingEdgeSource;
+use crate::hardware::{AnalogInput, Hardware, TimeSource};
+use crate::int0::Int0EdgeSource;
+use crate::millis::micros;
+use crate::time::Instant;
+use arduino_uno::adc::Adc;
+use arduino_uno::hal::port::mode::{Analog, Floating, Input, Output, PullUp};
+use arduino_uno::hal::port::portc::{PC4, PC5};
+use arduino_uno::hal::port::portd::PD2;
+use arduino_uno::hal::port::Pin;
+use arduino_uno::prelude::*;
+use arduino_uno::spi::Spi;
+use arduino_uno::Serial;
+use void::ResultVoidExt;
+use ws2812_spi::prerendered::Ws2812;
+
+pub type ClockInputPin = PD2<Input<Floating>>;
+pub type ResetInputPin = PC4<Input<Floating>>;
+pub type SequenceChangeInput = PC5<Input<PullUp>>;
+
+/// Peripherals of the Arduino Uno
+pub struct ArduinoUno {}
+
+impl Hardware for ArduinoUno {
+    type OutputPin = Pin<Output>;
+    type ClockInput = Int0EdgeSource;
+    type ResetInput = PollingEdgeSource<ResetInputPin>;
+    type RunInput = PollingEdgeSource<Pin<Input<Floating>>>;
+    type ButtonInput = SequenceChangeInput;
+    type Leds = Ws2812<'static, Spi<PullUp>>;
+    type Serial = Serial<Floating>;
+    type TimeSource = MicrosTimeSource;
+    type AnalogInput = AdcInput;
+}
+
+/// Time source backed by the TC0 interrupt counter in `millis`
+pub struct MicrosTimeSource {}
+
+impl TimeSource for MicrosTimeSource {
+    fn now(&self) -> Instant {
+        Instant::from_micros(micros())
+    }
+}
+
+/// Analog input on pin A4 (shared with the reset input, see the `reset_input` feature)
+pub struct AdcInput {
+    adc: Adc,
+    pin: PC4<Analog>,
+}
+
+impl AdcInput {
+    pub fn new(adc: Adc, pin: PC4<Analog>) -> Self {
+        Self { adc, pin }
+    }
+}
+
+impl AnalogInput for AdcInput {
+    fn read(&mut self) -> u16 {
+        nb::block!(self.adc.read(&mut self.pin)).void_unwrap()
+    }
+}
@@ -1,23 +1,34 @@
-use super::{ClockResult, ClockTrait, ExternalClock, InternalClock};
+use super::{
+    ClockFollower, ClockResult, ClockTrait, EdgeSource, ExternalClock, InternalClock, MixedClock,
+};
 use crate::sequence::Sequence;
 use crate::serial_wrapper::SerialWrapper;
-use arduino_uno::hal::port::mode::InputMode;
+use crate::time::{Duration, Instant};
+use ufmt::uWrite;
+use void::Void;
 
-pub enum Clock {
+pub enum Clock<IN: EdgeSource> {
     #[allow(unused)]
-    External(ExternalClock),
+    External(ExternalClock<IN>),
     #[allow(unused)]
     Internal(InternalClock),
+    #[allow(unused)]
+    Mixed(MixedClock<IN>),
+    #[allow(unused)]
+    Follower(ClockFollower<IN>),
 }
-impl ClockTrait for Clock {
-    fn check<IMODE: InputMode>(
+impl<IN: EdgeSource> ClockTrait for Clock<IN> {
+    fn check<S: uWrite<Error = Void>>(
         &mut self,
-        serial: &mut SerialWrapper<IMODE>,
-        sequence: Sequence,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+        sequence: &Sequence,
     ) -> ClockResult {
         match self {
-            Clock::External(c) => c.check(serial, sequence),
-            Clock::Internal(c) => c.check(serial, sequence),
+            Clock::External(c) => c.check(now, serial, sequence),
+            Clock::Internal(c) => c.check(now, serial, sequence),
+            Clock::Mixed(c) => c.check(now, serial, sequence),
+            Clock::Follower(c) => c.check(now, serial, sequence),
         }
     }
 
@@ -25,6 +36,53 @@ impl ClockTrait for Clock {
         match self {
             Clock::External(c) => c.reset(),
             Clock::Internal(c) => c.reset(),
+            Clock::Mixed(c) => c.reset(),
+            Clock::Follower(c) => c.reset(),
+        }
+    }
+
+    fn restart(&mut self) {
+        match self {
+            Clock::External(c) => c.restart(),
+            Clock::Internal(c) => c.restart(),
+            Clock::Mixed(c) => c.restart(),
+            Clock::Follower(c) => c.restart(),
+        }
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        match self {
+            Clock::External(c) => c.interval(),
+            Clock::Internal(c) => c.interval(),
+            Clock::Mixed(c) => c.interval(),
+            Clock::Follower(c) => c.interval(),
+        }
+    }
+
+    fn set_internal_interval(&mut self, interval: Duration) {
+        match self {
+            Clock::External(c) => c.set_internal_interval(interval),
+            Clock::Internal(c) => c.set_internal_interval(interval),
+            Clock::Mixed(c) => c.set_internal_interval(interval),
+            Clock::Follower(c) => c.set_internal_interval(interval),
+        }
+    }
+
+    fn set_swing(&mut self, swing: u8) {
+        match self {
+            Clock::External(c) => c.set_swing(swing),
+            Clock::Internal(c) => c.set_swing(swing),
+            Clock::Mixed(c) => c.set_swing(swing),
+            Clock::Follower(c) => c.set_swing(swing),
+        }
+    }
+
+    fn applies_swing(&self) -> bool {
+        match self {
+            Clock::External(c) => c.applies_swing(),
+            Clock::Internal(c) => c.applies_swing(),
+            Clock::Mixed(c) => c.applies_swing(),
+            Clock::Follower(c) => c.applies_swing(),
         }
     }
 }
@@ -0,0 +1,150 @@
+use crate::time::Instant;
+use embedded_hal::digital::v2::InputPin;
+use void::{ResultVoidExt, Void};
+
+/// Level change of the clock input
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ClockEdge {
+    /// `true` if the input went high
+    pub rising: bool,
+    /// Time when the edge happened
+    pub timestamp: Instant,
+}
+
+/// Source of the edges of the external clock input
+pub trait EdgeSource {
+    /// Return the oldest edge that was not yet consumed
+    fn next_edge(&mut self, now: Instant) -> Option<ClockEdge>;
+
+    /// Return the number of edges that were lost before they could be consumed
+    fn dropped(&self) -> u16 {
+        0
+    }
+}
+
+/// Edge source that detects edges by polling the input pin
+///
+/// The timestamp of an edge is the time it was detected, so edges between two polls are lost.
+pub struct PollingEdgeSource<IN: InputPin<Error = Void>> {
+    input: IN,
+    last_level: Option<bool>,
+}
+
+impl<IN: InputPin<Error = Void>> PollingEdgeSource<IN> {
+    pub fn new(input: IN) -> Self {
+        Self {
+            input,
+            last_level: None,
+        }
+    }
+}
+
+impl<IN: InputPin<Error = Void>> EdgeSource for PollingEdgeSource<IN> {
+    fn next_edge(&mut self, now: Instant) -> Option<ClockEdge> {
+        let level = self.input.is_high().void_unwrap();
+        if self.last_level == Some(level) {
+            return None;
+        }
+        self.last_level = Some(level);
+
+        Some(ClockEdge {
+            rising: level,
+            timestamp: now,
+        })
+    }
+}
+
+/// Number of edges that can be queued before new edges are dropped
+pub const EDGE_QUEUE_CAPACITY: usize = 8;
+
+/// Ring buffer of captured edges (e.g. filled from an interrupt handler)
+pub struct ClockEdgeQueue {
+    edges: [Option<ClockEdge>; EDGE_QUEUE_CAPACITY],
+    head: usize,
+    len: usize,
+    dropped: u16,
+}
+
+impl ClockEdgeQueue {
+    pub const fn new() -> Self {
+        Self {
+            edges: [None; EDGE_QUEUE_CAPACITY],
+            head: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Add the edge to the end of the queue
+    ///
+    /// If the queue is full the edge is dropped and counted in `dropped()`
+    pub fn push(&mut self, edge: ClockEdge) {
+        if self.len == EDGE_QUEUE_CAPACITY {
+            self.dropped = self.dropped.saturating_add(1);
+            return;
+        }
+        self.edges[(self.head + self.len) % EDGE_QUEUE_CAPACITY] = Some(edge);
+        self.len += 1;
+    }
+
+    /// Remove and return the oldest edge
+    pub fn pop(&mut self) -> Option<ClockEdge> {
+        if self.len == 0 {
+            return None;
+        }
+        let edge = self.edges[self.head].take();
+        self.head = (self.head + 1) % EDGE_QUEUE_CAPACITY;
+        self.len -= 1;
+
+        edge
+    }
+
+    /// Return the number of edges that were dropped because the queue was full
+    pub fn dropped(&self) -> u16 {
+        self.dropped
+    }
+}
+
+impl EdgeSource for ClockEdgeQueue {
+    fn next_edge(&mut self, _now: Instant) -> Option<ClockEdge> {
+        self.pop()
+    }
+
+    fn dropped(&self) -> u16 {
+        ClockEdgeQueue::dropped(self)
+    }
+}
+
+impl Default for ClockEdgeQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn edge(rising: bool, micros: u32) -> ClockEdge {
+        ClockEdge {
+            rising,
+            timestamp: Instant::from_micros(micros),
+        }
+    }
+
+    #[test]
+    fn queue_keeps_edges_in_order_and_drops_overflow() {
+        let mut queue = ClockEdgeQueue::new();
+        for i in 0..EDGE_QUEUE_CAPACITY as u32 + 2 {
+            queue.push(edge(i % 2 == 0, i));
+        }
+        assert_eq!(queue.dropped(), 2);
+
+        assert_eq!(queue.pop(), Some(edge(true, 0)));
+        assert_eq!(queue.pop(), Some(edge(false, 1)));
+        queue.push(edge(true, 100));
+        let remaining: Vec<_> = core::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(remaining.len(), EDGE_QUEUE_CAPACITY - 1);
+        assert_eq!(remaining.last(), Some(&edge(true, 100)));
+    }
+}
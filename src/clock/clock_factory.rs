@@ -1,35 +1,133 @@
 use super::ClockTrait;
 #[allow(unused_imports)]
-use crate::clock::{Clock, ExternalClock, InternalClock};
-use crate::{DELAY_TIME, USE_INTERNAL_CLOCK};
-use arduino_uno::hal::port::mode::{Floating, Input};
-use arduino_uno::hal::port::portd::PD2;
+use crate::clock::{
+    Clock, ClockFollower, ClockMode, ClockRatio, EdgeSource, ExternalClock, InternalClock,
+    MixedClock, RatioClock,
+};
+use crate::random::DEFAULT_SEED;
+use crate::time::Duration;
+use crate::{CLOCK_MODE, CLOCK_RATIO, DELAY_TIME, EXTERNAL_CLOCK_TIMEOUT, INTERNAL_CLOCK_INTERVAL};
 use core::marker::PhantomData;
 
 pub struct ClockFactory<CLOCK: ClockTrait> {
+    clock_mode: ClockMode,
+    external_clock_timeout: Duration,
+    clock_ratio: ClockRatio,
+    internal_interval: Duration,
+    swing: u8,
+    external_swing: bool,
+    seed: Option<u32>,
     _phantom: PhantomData<CLOCK>,
 }
 
-impl<CLOCK: ClockTrait> ClockFactory<CLOCK> {
-    pub fn build(&self, trigger_input: PD2<Input<Floating>>) -> Clock {
-        if USE_INTERNAL_CLOCK {
-            Clock::Internal(InternalClock::new(250, DELAY_TIME))
-        } else {
-            Clock::External(ExternalClock::new(trigger_input))
-        }
+impl<IN: EdgeSource> ClockFactory<Clock<IN>> {
+    pub fn build(&self, trigger_input: IN) -> Clock<IN> {
+        self.build_clock(trigger_input)
+    }
+}
+
+impl<IN: EdgeSource> ClockFactory<RatioClock<Clock<IN>>> {
+    pub fn build(&self, trigger_input: IN) -> RatioClock<Clock<IN>> {
+        let mut clock = RatioClock::new(
+            self.build_clock(trigger_input),
+            self.clock_ratio,
+            self.seed.unwrap_or(DEFAULT_SEED),
+        );
+        clock.set_external_swing(self.external_swing);
+        clock.set_swing(self.swing);
+        clock
     }
+}
 
+impl<CLOCK: ClockTrait> ClockFactory<CLOCK> {
     pub fn new() -> Self {
+        Self::with_clock_mode(CLOCK_MODE)
+    }
+
+    /// Create a factory that overrides the `CLOCK_MODE` setting
+    pub fn with_clock_mode(clock_mode: ClockMode) -> Self {
         Self {
+            clock_mode,
+            external_clock_timeout: EXTERNAL_CLOCK_TIMEOUT,
+            clock_ratio: CLOCK_RATIO,
+            internal_interval: INTERNAL_CLOCK_INTERVAL,
+            swing: 0,
+            external_swing: false,
+            seed: None,
             _phantom: Default::default(),
         }
     }
+
+    /// Create a factory that only uses the internal or only the external clock
+    pub fn with_internal_clock(use_internal_clock: bool) -> Self {
+        Self::with_clock_mode(if use_internal_clock {
+            ClockMode::Internal
+        } else {
+            ClockMode::External
+        })
+    }
+
+    /// Override the `EXTERNAL_CLOCK_TIMEOUT` after which `ClockMode::Mixed` falls back to the
+    /// internal clock
+    pub fn with_external_clock_timeout(mut self, timeout: Duration) -> Self {
+        self.external_clock_timeout = timeout;
+        self
+    }
+
+    /// Override the `INTERNAL_CLOCK_INTERVAL` the internal clock starts with
+    pub fn with_internal_interval(mut self, interval: Duration) -> Self {
+        self.internal_interval = interval;
+        self
+    }
+
+    /// Set the swing in percent of the interval
+    pub fn with_swing(mut self, swing: u8) -> Self {
+        self.swing = swing;
+        self
+    }
+
+    /// Also apply the swing to clocks without an own swing (e.g. the external clock)
+    pub fn with_external_swing(mut self, external_swing: bool) -> Self {
+        self.external_swing = external_swing;
+        self
+    }
+
+    /// Use the given seed for the random directions of the playhead
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Override the `CLOCK_RATIO` between the clock-triggers and the steps
+    pub fn with_clock_ratio(mut self, clock_ratio: ClockRatio) -> Self {
+        self.clock_ratio = clock_ratio;
+        self
+    }
+}
+
+impl<CLOCK: ClockTrait> ClockFactory<CLOCK> {
+    fn build_clock<IN: EdgeSource>(&self, trigger_input: IN) -> Clock<IN> {
+        let internal_clock = InternalClock::new(self.internal_interval, DELAY_TIME);
+        let mut clock = match self.clock_mode {
+            ClockMode::Internal => Clock::Internal(internal_clock),
+            ClockMode::External => Clock::External(ExternalClock::new(trigger_input)),
+            ClockMode::Mixed => Clock::Mixed(MixedClock::new(
+                internal_clock,
+                ExternalClock::new(trigger_input),
+                self.external_clock_timeout,
+            )),
+            ClockMode::Follow => Clock::Follower(ClockFollower::new(
+                ExternalClock::new(trigger_input),
+                DELAY_TIME,
+            )),
+        };
+        clock.set_swing(self.swing);
+        clock
+    }
 }
 
 impl<CLOCK: ClockTrait> Default for ClockFactory<CLOCK> {
     fn default() -> Self {
-        Self {
-            _phantom: Default::default(),
-        }
+        Self::new()
     }
 }
@@ -0,0 +1,181 @@
+use crate::clock::{ClockResult, ClockTrait, EdgeSource, ExternalClock, TempoEstimator};
+use crate::sequence::Sequence;
+use crate::serial_wrapper::SerialWrapper;
+use crate::time::{Duration, Instant};
+use crate::trigger_state::TriggerState;
+use ufmt::uWrite;
+use void::{ResultVoidExt, Void};
+
+/// Clock that follows the external clock and keeps running at the estimated tempo if it stops
+///
+/// The external clock is considered stopped if an edge is overdue by half an interval. While
+/// running freely, the clock-triggers stay on the grid of the last external edge.
+pub struct ClockFollower<IN: EdgeSource> {
+    external: ExternalClock<IN>,
+    estimator: TempoEstimator,
+    /// Duration how long the trigger will be held high while running freely
+    hold_time: Duration,
+    /// Timestamp of the last (external or generated) clock-trigger
+    last_tick: Option<Instant>,
+    /// `true` while clock-triggers are generated from the estimated tempo
+    free_running: bool,
+    /// `true` while a generated clock-trigger is held high
+    high: bool,
+}
+
+impl<IN: EdgeSource> ClockFollower<IN> {
+    pub fn new(external: ExternalClock<IN>, hold_time: Duration) -> Self {
+        Self {
+            external,
+            estimator: TempoEstimator::new(),
+            hold_time,
+            last_tick: None,
+            free_running: false,
+            high: false,
+        }
+    }
+
+    /// Return if the clock-triggers are currently generated from the estimated tempo
+    pub fn is_free_running(&self) -> bool {
+        self.free_running
+    }
+
+    fn external_rise<S: uWrite<Error = Void>>(
+        &mut self,
+        timestamp: Instant,
+        serial: &mut SerialWrapper<S>,
+    ) -> TriggerState {
+        let last_tick = self.last_tick.replace(timestamp);
+        if !self.free_running {
+            if let Some(last_tick) = last_tick {
+                self.estimator.update(timestamp.duration_since(last_tick));
+            }
+            return TriggerState::Rise;
+        }
+
+        ufmt::uwriteln!(serial, "EXT CLK\r").void_unwrap();
+        self.free_running = false;
+        match (last_tick, self.estimator.estimate()) {
+            // The edge belongs to the step that was just generated
+            (Some(last_tick), Some(interval))
+                if timestamp.duration_since(last_tick) < interval / 2 =>
+            {
+                TriggerState::Unchanged
+            }
+            _ => TriggerState::Rise,
+        }
+    }
+
+    fn generated_trigger_state<S: uWrite<Error = Void>>(
+        &mut self,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+    ) -> (TriggerState, Instant) {
+        let (last_tick, interval) = match (self.last_tick, self.estimator.estimate()) {
+            (Some(last_tick), Some(interval)) => (last_tick, interval),
+            _ => return (TriggerState::Unchanged, now),
+        };
+
+        let elapsed = now.duration_since(last_tick);
+        if !self.free_running && elapsed >= interval + interval / 2 {
+            ufmt::uwriteln!(serial, "FREE RUN\r").void_unwrap();
+            self.free_running = true;
+        }
+        if !self.free_running {
+            return (TriggerState::Unchanged, now);
+        }
+
+        if elapsed >= interval {
+            let tick = last_tick + interval;
+            self.last_tick = Some(tick);
+            self.high = true;
+            (TriggerState::Rise, tick)
+        } else if self.high && elapsed >= self.hold_time {
+            self.high = false;
+            (TriggerState::Fall, now)
+        } else {
+            (TriggerState::Unchanged, now)
+        }
+    }
+}
+
+impl<IN: EdgeSource> ClockTrait for ClockFollower<IN> {
+    fn check<S: uWrite<Error = Void>>(
+        &mut self,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+        sequence: &Sequence,
+    ) -> ClockResult {
+        let external = self.external.check(now, serial, sequence);
+        let (trigger_state, timestamp) = match external.trigger_state {
+            TriggerState::Rise => (
+                self.external_rise(external.timestamp, serial),
+                external.timestamp,
+            ),
+            TriggerState::Fall => {
+                self.high = false;
+                (TriggerState::Fall, external.timestamp)
+            }
+            TriggerState::Unchanged => self.generated_trigger_state(now, serial),
+        };
+
+        ClockResult {
+            trigger_state,
+            step_counter: 0,
+            wrapped: false,
+            timestamp,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.external.reset()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.estimator.estimate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::PollingEdgeSource;
+    use crate::mock::{MockInputPin, MockSerial};
+    use crate::seq;
+
+    #[test]
+    fn continues_at_estimated_tempo_when_external_clock_stops() {
+        let input = MockInputPin::default();
+        let mut clock = ClockFollower::new(
+            ExternalClock::new(PollingEdgeSource::new(input.clone())),
+            Duration::from_millis(5),
+        );
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 15, 15, 15);
+        let mut rises = vec![];
+        for millis in 0..1000 {
+            // Four external clock pulses every 100ms, then the clock stops
+            input.set(millis < 400 && millis % 100 < 50);
+            let result = clock.check(Instant::from_millis(millis), &mut serial, &sequence);
+            if result.trigger_state == TriggerState::Rise {
+                rises.push((millis, result.timestamp.as_micros() / 1000));
+            }
+        }
+
+        assert_eq!(
+            rises,
+            [
+                (0, 0),
+                (100, 100),
+                (200, 200),
+                (300, 300),
+                (450, 400),
+                (500, 500),
+                (600, 600),
+                (700, 700),
+                (800, 800),
+                (900, 900)
+            ]
+        );
+    }
+}
@@ -1,60 +1,58 @@
-use crate::clock::{ClockResult, ClockTrait, StepCounterType};
+use crate::clock::{ClockEdge, ClockResult, ClockTrait, EdgeSource};
 use crate::sequence::Sequence;
 use crate::serial_wrapper::SerialWrapper;
+use crate::time::{Duration, Instant};
 use crate::trigger_state::TriggerState;
-use arduino_uno::hal::port::mode::{Floating, Input, InputMode};
-use arduino_uno::hal::port::portd::PD2;
-use arduino_uno::prelude::*;
-use void::ResultVoidExt;
+use ufmt::uWrite;
+use void::{ResultVoidExt, Void};
 
-type ClockInput = PD2<Input<Floating>>;
-
-pub struct ExternalClock {
-    input: ClockInput,
-    step_counter: StepCounterType,
+pub struct ExternalClock<IN: EdgeSource> {
+    input: IN,
     last_important_trigger_state: TriggerState,
+    /// Number of dropped edges that were already reported
+    reported_dropped: u16,
 }
 
-impl ExternalClock {
+impl<IN: EdgeSource> ExternalClock<IN> {
     #[allow(unused)]
-    pub fn new(input: ClockInput) -> Self {
+    pub fn new(input: IN) -> Self {
         Self {
             input,
-            step_counter: 0,
             last_important_trigger_state: TriggerState::Unchanged,
+            reported_dropped: 0,
         }
     }
 
-    fn get_new_trigger_state(&self) -> TriggerState {
-        let trigger_pin_state = self.input.is_high().void_unwrap();
-
-        match trigger_pin_state {
-            true if self.last_important_trigger_state != TriggerState::Rise => TriggerState::Rise,
-            false if self.last_important_trigger_state != TriggerState::Fall => TriggerState::Fall,
+    fn get_new_trigger_state(&self, edge: Option<ClockEdge>) -> TriggerState {
+        match edge.map(|edge| edge.rising) {
+            Some(true) if self.last_important_trigger_state != TriggerState::Rise => {
+                TriggerState::Rise
+            }
+            Some(false) if self.last_important_trigger_state != TriggerState::Fall => {
+                TriggerState::Fall
+            }
             _ => TriggerState::Unchanged,
         }
     }
-
-    fn advance_step_counter(&mut self, sequence: Sequence) {
-        if self.step_counter < (sequence.len() as StepCounterType) - 1 {
-            self.step_counter += 1
-        } else {
-            self.step_counter = 0
-        }
-    }
 }
 
-impl ClockTrait for ExternalClock {
-    fn check<IMODE: InputMode>(
+impl<IN: EdgeSource> ClockTrait for ExternalClock<IN> {
+    fn check<S: uWrite<Error = Void>>(
         &mut self,
-        serial: &mut SerialWrapper<IMODE>,
-        sequence: Sequence,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+        _sequence: &Sequence,
     ) -> ClockResult {
-        let trigger_state = self.get_new_trigger_state();
+        // Only one edge is consumed per call, so queued edges are handled in the next calls
+        let edge = self.input.next_edge(now);
+        let dropped = self.input.dropped();
+        if dropped != self.reported_dropped {
+            ufmt::uwriteln!(serial, "dropped {} edges\r", dropped).void_unwrap();
+            self.reported_dropped = dropped;
+        }
+        let trigger_state = self.get_new_trigger_state(edge);
         match trigger_state {
             TriggerState::Rise => {
-                self.advance_step_counter(sequence);
-
                 ufmt::uwriteln!(serial, "CLK!\r").void_unwrap();
 
                 self.last_important_trigger_state = trigger_state
@@ -65,11 +63,47 @@ impl ClockTrait for ExternalClock {
 
         ClockResult {
             trigger_state,
-            step_counter: self.step_counter,
+            step_counter: 0,
+            wrapped: false,
+            timestamp: edge.map_or(now, |edge| edge.timestamp),
         }
     }
 
     fn reset(&mut self) {
-        self.step_counter = 0
+        // The edges are passed on as they come, there is no state to reset
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        // The interval is only known after measuring it
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::clock_edge::EDGE_QUEUE_CAPACITY;
+    use crate::clock::ClockEdgeQueue;
+    use crate::mock::MockSerial;
+    use crate::seq;
+
+    #[test]
+    fn reports_dropped_edges() {
+        let mut queue = ClockEdgeQueue::new();
+        for i in 0..EDGE_QUEUE_CAPACITY as u32 + 2 {
+            queue.push(ClockEdge {
+                rising: i % 2 == 0,
+                timestamp: Instant::from_micros(i),
+            });
+        }
+        let mock_serial = MockSerial::new();
+        let mut serial = SerialWrapper::new(true, mock_serial.clone());
+        let sequence = seq!(15, 15);
+        let mut clock = ExternalClock::new(queue);
+
+        clock.check(Instant::from_millis(1), &mut serial, &sequence);
+        assert!(mock_serial.take_output().starts_with("dropped"));
+        clock.check(Instant::from_millis(2), &mut serial, &sequence);
+        assert!(!mock_serial.take_output().contains("dropped"));
     }
 }
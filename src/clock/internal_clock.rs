@@ -1,73 +1,143 @@
-use crate::clock::{ClockTrait, ClockResult, StepCounterType};
-use crate::millis::millis;
+use crate::clock::{ClockResult, ClockTrait};
 use crate::sequence::Sequence;
 use crate::serial_wrapper::SerialWrapper;
+use crate::time::{Duration, Instant};
 use crate::trigger_state::TriggerState;
-use arduino_uno::hal::port::mode::InputMode;
-use void::ResultVoidExt;
+use crate::MAX_SWING;
+use ufmt::uWrite;
+use void::{ResultVoidExt, Void};
 
 pub struct InternalClock {
-    /// Interval between clock-triggers in milliseconds
-    interval: u32,
-    /// Timestamp in milliseconds when the last clock-trigger happened
-    last_tick_timestamp: u32,
-    /// Duration in milliseconds how long the trigger will be held high
-    hold_time: u32,
-    step_counter: StepCounterType,
+    /// Interval between clock-triggers
+    interval: Duration,
+    /// Timestamp of the last clock-trigger on the straight (not swung) grid
+    last_tick_timestamp: Instant,
+    /// Timestamp when the last clock-trigger happened
+    last_rise_timestamp: Instant,
+    /// Duration how long the trigger will be held high
+    hold_time: Duration,
+    /// Delay of every second clock-trigger in percent of the interval
+    swing: u8,
+    /// `true` if the next clock-trigger is delayed by the swing
+    offbeat: bool,
 }
 
 impl InternalClock {
-    /// Create a new internal clock which triggers every `interval` milliseconds
-    pub fn new(interval: u32, hold_time: u32) -> Self {
+    /// Create a new internal clock which triggers every `interval`
+    pub fn new(interval: Duration, hold_time: Duration) -> Self {
         Self {
-            step_counter: 0,
-            last_tick_timestamp: 0,
+            last_tick_timestamp: Instant::from_millis(0),
+            last_rise_timestamp: Instant::from_millis(0),
             interval,
             hold_time,
+            swing: 0,
+            // The first clock-trigger plays the second step
+            offbeat: true,
         }
     }
 
-    fn get_new_trigger_state(&mut self) -> TriggerState {
-        let current_timestamp = millis();
-        if self.last_tick_timestamp + self.interval <= current_timestamp {
-            self.last_tick_timestamp = current_timestamp;
+    fn get_new_trigger_state(&mut self, current_timestamp: Instant) -> TriggerState {
+        let delay = if self.offbeat {
+            self.interval * self.swing as u32 / 100
+        } else {
+            Duration::from_micros(0)
+        };
+        let elapsed = current_timestamp.duration_since(self.last_tick_timestamp);
+        if elapsed >= self.interval + delay {
+            self.last_tick_timestamp = current_timestamp - delay;
+            self.last_rise_timestamp = current_timestamp;
+            self.offbeat = !self.offbeat;
             TriggerState::Rise
-        } else if self.last_tick_timestamp + self.hold_time <= current_timestamp {
+        } else if current_timestamp.duration_since(self.last_rise_timestamp) >= self.hold_time {
             TriggerState::Fall
         } else {
             TriggerState::Unchanged
         }
     }
-
-    fn advance_step_counter(&mut self, sequence: Sequence) {
-        if self.step_counter < (sequence.len() as StepCounterType) - 1 {
-            self.step_counter += 1
-        } else {
-            self.step_counter = 0
-        }
-    }
 }
 
 impl ClockTrait for InternalClock {
-    fn check<IMODE: InputMode>(
+    fn check<S: uWrite<Error = Void>>(
         &mut self,
-        serial: &mut SerialWrapper<IMODE>,
-        sequence: Sequence,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+        _sequence: &Sequence,
     ) -> ClockResult {
-        let trigger_state = self.get_new_trigger_state();
+        let trigger_state = self.get_new_trigger_state(now);
         if let TriggerState::Rise = trigger_state {
-            self.advance_step_counter(sequence);
-
             ufmt::uwriteln!(serial, "CLK!\r").void_unwrap();
         }
 
         ClockResult {
             trigger_state,
-            step_counter: self.step_counter,
+            step_counter: 0,
+            wrapped: false,
+            timestamp: now,
         }
     }
 
     fn reset(&mut self) {
-        self.step_counter = 0
+        self.offbeat = true
+    }
+
+    fn restart(&mut self) {
+        self.offbeat = false
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+
+    fn set_internal_interval(&mut self, interval: Duration) {
+        self.interval = interval
+    }
+
+    fn set_swing(&mut self, swing: u8) {
+        self.swing = swing.min(MAX_SWING)
+    }
+
+    fn applies_swing(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::MockSerial;
+    use crate::seq;
+
+    #[test]
+    fn triggers_across_counter_overflow() {
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 0, 15, 0);
+        let mut clock = InternalClock::new(Duration::from_millis(250), Duration::from_millis(5));
+        let mut check = |micros| {
+            clock
+                .check(Instant::from_micros(micros), &mut serial, &sequence)
+                .trigger_state
+        };
+
+        assert!(check(u32::MAX - 100_000) == TriggerState::Rise);
+        assert!(check(u32::MAX - 90_000) == TriggerState::Fall);
+        assert!(check(149_998) == TriggerState::Fall);
+        assert!(check(149_999) == TriggerState::Rise);
+        assert!(check(150_000) == TriggerState::Unchanged);
+    }
+
+    #[test]
+    fn swing_delays_every_second_clock_trigger() {
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 0, 15, 0);
+        let mut clock = InternalClock::new(Duration::from_millis(100), Duration::from_millis(5));
+        clock.set_swing(50);
+
+        let rises: Vec<u32> = (0..=600)
+            .filter(|millis| {
+                let result = clock.check(Instant::from_millis(*millis), &mut serial, &sequence);
+                result.trigger_state == TriggerState::Rise
+            })
+            .collect();
+        assert_eq!(rises, [150, 200, 350, 400, 550, 600]);
     }
 }
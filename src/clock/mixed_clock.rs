@@ -0,0 +1,133 @@
+use crate::clock::{ClockResult, ClockTrait, EdgeSource, ExternalClock, InternalClock};
+use crate::sequence::Sequence;
+use crate::serial_wrapper::SerialWrapper;
+use crate::time::{Duration, Instant};
+use crate::trigger_state::TriggerState;
+use ufmt::uWrite;
+use void::{ResultVoidExt, Void};
+
+/// Clock that free-runs on the internal clock until an external clock is patched
+///
+/// A rising edge on the external input switches to the external clock. If no edge arrives for
+/// `timeout` the clock falls back to the internal clock.
+pub struct MixedClock<IN: EdgeSource> {
+    internal: InternalClock,
+    external: ExternalClock<IN>,
+    /// Duration without external edges after which the internal clock takes over again
+    timeout: Duration,
+    /// Timestamp of the last external edge while following the external clock
+    last_external_edge: Option<Instant>,
+}
+
+impl<IN: EdgeSource> MixedClock<IN> {
+    pub fn new(internal: InternalClock, external: ExternalClock<IN>, timeout: Duration) -> Self {
+        Self {
+            internal,
+            external,
+            timeout,
+            last_external_edge: None,
+        }
+    }
+
+    /// Return if the external clock is currently followed
+    pub fn is_external(&self) -> bool {
+        self.last_external_edge.is_some()
+    }
+}
+
+impl<IN: EdgeSource> ClockTrait for MixedClock<IN> {
+    fn check<S: uWrite<Error = Void>>(
+        &mut self,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+        sequence: &Sequence,
+    ) -> ClockResult {
+        // The external clock is always checked to consume its edges
+        let external = self.external.check(now, serial, sequence);
+        match (self.last_external_edge, external.trigger_state) {
+            (Some(_), TriggerState::Rise) | (Some(_), TriggerState::Fall) => {
+                self.last_external_edge = Some(external.timestamp);
+                external
+            }
+            (None, TriggerState::Rise) => {
+                ufmt::uwriteln!(serial, "EXT CLK\r").void_unwrap();
+                self.last_external_edge = Some(external.timestamp);
+                external
+            }
+            (Some(last_edge), _) if now.duration_since(last_edge) < self.timeout => external,
+            (Some(_), _) => {
+                ufmt::uwriteln!(serial, "INT CLK\r").void_unwrap();
+                self.last_external_edge = None;
+                self.internal.check(now, serial, sequence)
+            }
+            (None, _) => self.internal.check(now, serial, sequence),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.internal.reset();
+        self.external.reset()
+    }
+
+    fn restart(&mut self) {
+        self.internal.restart();
+        self.external.reset()
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        if self.is_external() {
+            None
+        } else {
+            self.internal.interval()
+        }
+    }
+
+    fn set_internal_interval(&mut self, interval: Duration) {
+        self.internal.set_internal_interval(interval)
+    }
+
+    fn set_swing(&mut self, swing: u8) {
+        self.internal.set_swing(swing)
+    }
+
+    fn applies_swing(&self) -> bool {
+        !self.is_external()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::PollingEdgeSource;
+    use crate::mock::{MockInputPin, MockSerial};
+    use crate::seq;
+
+    #[test]
+    fn switches_to_external_clock_and_falls_back_after_timeout() {
+        let input = MockInputPin::default();
+        let mut clock = MixedClock::new(
+            InternalClock::new(Duration::from_millis(100), Duration::from_millis(5)),
+            ExternalClock::new(PollingEdgeSource::new(input.clone())),
+            Duration::from_millis(500),
+        );
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 15, 15, 15);
+        let mut check = |millis| {
+            clock
+                .check(Instant::from_millis(millis), &mut serial, &sequence)
+                .trigger_state
+        };
+
+        assert!(check(0) == TriggerState::Unchanged);
+        assert!(check(100) == TriggerState::Rise);
+        input.set(true);
+        assert!(check(150) == TriggerState::Rise);
+        // The internal clock is ignored while the external clock is followed
+        assert!(check(200) == TriggerState::Unchanged);
+        input.set(false);
+        assert!(check(220) == TriggerState::Fall);
+        assert!(check(719) == TriggerState::Unchanged);
+        assert!(check(720) == TriggerState::Rise);
+        assert!(check(820) == TriggerState::Rise);
+    }
+}
@@ -1,31 +1,109 @@
+use crate::playhead::Direction;
 use crate::sequence::Sequence;
 use crate::trigger_state::TriggerState;
 
 mod clock;
+mod clock_edge;
 mod clock_factory;
+mod clock_follower;
 mod external_clock;
 mod internal_clock;
+mod mixed_clock;
+mod ratio_clock;
+mod tempo_estimator;
 
 use crate::serial_wrapper::SerialWrapper;
-use arduino_uno::hal::port::mode::InputMode;
+use crate::time::{Duration, Instant};
 pub use clock::Clock;
+pub use clock_edge::{ClockEdge, ClockEdgeQueue, EdgeSource, PollingEdgeSource};
 pub use clock_factory::ClockFactory;
+pub use clock_follower::ClockFollower;
+use core::str::FromStr;
 pub use external_clock::ExternalClock;
 pub use internal_clock::InternalClock;
+pub use mixed_clock::MixedClock;
+pub use ratio_clock::{ClockRatio, RatioClock};
+pub use tempo_estimator::TempoEstimator;
+use ufmt::uWrite;
+use void::Void;
 
 pub type StepCounterType = usize;
 
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClockMode {
+    /// Only use the internal clock
+    Internal,
+    /// Only follow the external clock on `PD2`
+    External,
+    /// Use the internal clock until an external clock is patched
+    Mixed,
+    /// Follow the external clock and keep running at its tempo if it stops
+    Follow,
+}
+
+impl FromStr for ClockMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "internal" => Ok(ClockMode::Internal),
+            "external" => Ok(ClockMode::External),
+            "mixed" => Ok(ClockMode::Mixed),
+            "follow" => Ok(ClockMode::Follow),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Result of a `ClockTrait::check()`
+///
+/// `step_counter` and `wrapped` come from the playhead of `RatioClock`. The clocks it wraps only
+/// generate the clock-triggers and leave them at `0` and `false`.
 pub struct ClockResult {
     pub trigger_state: TriggerState,
     pub step_counter: StepCounterType,
+    /// `true` if this clock-trigger wrapped the playhead around to the start of the loop
+    pub wrapped: bool,
+    /// Time when the `trigger_state` changed
+    pub timestamp: Instant,
 }
 
 pub trait ClockTrait {
-    fn check<IMODE: InputMode>(
+    fn check<S: uWrite<Error = Void>>(
         &mut self,
-        serial: &mut SerialWrapper<IMODE>,
-        sequence: Sequence,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+        sequence: &Sequence,
     ) -> ClockResult;
 
     fn reset(&mut self);
+
+    /// Reset the step counter so that the next clock-trigger plays the first step
+    fn restart(&mut self) {
+        self.reset()
+    }
+
+    /// Return the interval between clock-triggers if it is known in advance
+    fn interval(&self) -> Option<Duration>;
+
+    /// Change the interval of the internal clock (ignored by clocks without one)
+    fn set_internal_interval(&mut self, _interval: Duration) {}
+
+    /// Move the playhead to the first step of `sequence` and return it, so that the current
+    /// clock-trigger plays it (ignored by clocks without a playhead)
+    fn start(&mut self, _sequence: &Sequence) -> StepCounterType {
+        0
+    }
+
+    /// Override the direction of the sequences (`None` uses the direction of each sequence;
+    /// ignored by clocks without a playhead)
+    fn set_direction(&mut self, _direction: Option<Direction>) {}
+
+    /// Delay every second clock-trigger by `swing` percent of the interval
+    fn set_swing(&mut self, _swing: u8) {}
+
+    /// Return if the clock currently applies the swing itself
+    fn applies_swing(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,474 @@
+use crate::clock::{ClockResult, ClockTrait, StepCounterType, TempoEstimator};
+use crate::playhead::{Direction, Playhead};
+use crate::scheduler::{Task, TaskId, TaskQueue};
+use crate::sequence::Sequence;
+use crate::serial_wrapper::SerialWrapper;
+use crate::time::{Duration, Instant};
+use crate::trigger_state::TriggerState;
+use crate::MAX_SWING;
+use core::str::FromStr;
+use ufmt::uWrite;
+use void::Void;
+
+/// Ratio between the clock-triggers of a clock and the steps of the sequence
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClockRatio {
+    /// Advance one step every `n` clock-triggers
+    Divide(u8),
+    /// Advance `n` evenly spaced steps per clock-trigger
+    Multiply(u8),
+}
+
+impl ClockRatio {
+    /// Return the ratio with a factor of at least 1
+    fn normalized(self) -> Self {
+        match self {
+            ClockRatio::Divide(n) => ClockRatio::Divide(n.max(1)),
+            ClockRatio::Multiply(n) => ClockRatio::Multiply(n.max(1)),
+        }
+    }
+}
+
+impl Default for ClockRatio {
+    fn default() -> Self {
+        ClockRatio::Multiply(1)
+    }
+}
+
+impl FromStr for ClockRatio {
+    type Err = ();
+
+    /// Parse a ratio like `/2` or `x3`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let factor = |digits: &str| digits.parse::<u8>().ok().filter(|n| *n > 0).ok_or(());
+        match s.get(..1) {
+            Some("/") => Ok(ClockRatio::Divide(factor(&s[1..])?)),
+            Some("x") | Some("*") => Ok(ClockRatio::Multiply(factor(&s[1..])?)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Clock-trigger edges that are delayed by the swing
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SwingTask {
+    Rise,
+    Fall,
+}
+
+impl TaskId for SwingTask {}
+
+/// Clock that divides or multiplies the clock-triggers of another clock before advancing the
+/// steps
+///
+/// Multiplied clock-triggers are interpolated from the interval of the inner clock, or from the
+/// interval measured between its clock-triggers if the inner clock does not know it. The
+/// `RatioClock` owns the playhead that advances the steps.
+///
+/// If external swing is enabled, every second clock-trigger of a clock that does not apply the
+/// swing itself (e.g. an external clock) is delayed through the scheduler.
+pub struct RatioClock<CLOCK: ClockTrait> {
+    clock: CLOCK,
+    ratio: ClockRatio,
+    estimator: TempoEstimator,
+    last_inner_rise: Option<Instant>,
+    /// Number of inner clock-triggers since the last passed one (`ClockRatio::Divide`)
+    rise_counter: u8,
+    /// `true` if the last inner clock-trigger was passed on (`ClockRatio::Divide`)
+    passed_rise: bool,
+    /// `true` if clock-triggers are interpolated since the last inner one (`ClockRatio::Multiply`)
+    interpolating: bool,
+    pending_ticks: u8,
+    next_tick: Instant,
+    tick_interval: Duration,
+    fall_at: Option<Instant>,
+    playhead: Playhead,
+    /// `true` if the playhead moves to the start of the sequence with the next check
+    rewind: bool,
+    /// `true` if the next clock-trigger plays the first step instead of advancing
+    restart: bool,
+    swing: u8,
+    external_swing: bool,
+    /// `true` if the next clock-trigger is delayed by the swing
+    offbeat: bool,
+    /// `true` if the rise of the current pulse was delayed by the swing, so its fall is as well
+    swung_pulse: bool,
+    swing_delay: Duration,
+    /// Edges that are delayed by the swing
+    swung_edges: TaskQueue<SwingTask, 4>,
+}
+
+impl<CLOCK: ClockTrait> RatioClock<CLOCK> {
+    /// Create a clock that divides or multiplies `clock` by `ratio`
+    ///
+    /// `seed` seeds the random directions of the playhead.
+    pub fn new(clock: CLOCK, ratio: ClockRatio, seed: u32) -> Self {
+        Self {
+            clock,
+            ratio: ratio.normalized(),
+            estimator: TempoEstimator::new(),
+            last_inner_rise: None,
+            rise_counter: 0,
+            passed_rise: false,
+            interpolating: false,
+            pending_ticks: 0,
+            next_tick: Instant::from_micros(0),
+            tick_interval: Duration::from_micros(0),
+            fall_at: None,
+            playhead: Playhead::new(seed),
+            rewind: true,
+            restart: false,
+            swing: 0,
+            external_swing: false,
+            offbeat: true,
+            swung_pulse: false,
+            swing_delay: Duration::from_micros(0),
+            swung_edges: TaskQueue::new(),
+        }
+    }
+
+    /// Enable or disable the swing for clocks that do not apply it themselves
+    pub fn set_external_swing(&mut self, external_swing: bool) {
+        self.external_swing = external_swing
+    }
+
+    /// Change the ratio, which takes effect with the next clock-trigger of the inner clock
+    pub fn set_ratio(&mut self, ratio: ClockRatio) {
+        self.ratio = ratio.normalized()
+    }
+
+    pub fn ratio(&self) -> ClockRatio {
+        self.ratio
+    }
+
+    /// Return the interval of the inner clock
+    fn inner_interval(&self) -> Option<Duration> {
+        self.clock.interval().or_else(|| self.estimator.estimate())
+    }
+
+    fn divide(&mut self, inner: &ClockResult, n: u8) -> TriggerState {
+        match inner.trigger_state {
+            TriggerState::Rise => {
+                self.passed_rise = self.rise_counter == 0;
+                self.rise_counter = (self.rise_counter + 1) % n;
+                if self.passed_rise {
+                    TriggerState::Rise
+                } else {
+                    TriggerState::Unchanged
+                }
+            }
+            TriggerState::Fall if self.passed_rise => TriggerState::Fall,
+            _ => TriggerState::Unchanged,
+        }
+    }
+
+    fn multiply(&mut self, inner: &ClockResult, n: u8, now: Instant) -> (TriggerState, Instant) {
+        if inner.trigger_state == TriggerState::Rise {
+            self.pending_ticks = 0;
+            self.fall_at = None;
+            self.interpolating = false;
+            if let (Some(interval), true) = (self.inner_interval(), n > 1) {
+                self.interpolating = true;
+                self.tick_interval = interval / n as u32;
+                self.next_tick = inner.timestamp + self.tick_interval;
+                self.pending_ticks = n - 1;
+                self.fall_at = Some(inner.timestamp + self.tick_interval / 2);
+            }
+            return (TriggerState::Rise, inner.timestamp);
+        }
+        if !self.interpolating {
+            return (inner.trigger_state, inner.timestamp);
+        }
+
+        match self.fall_at {
+            Some(fall_at) if now.is_at_or_after(fall_at) => {
+                self.fall_at = None;
+                (TriggerState::Fall, fall_at)
+            }
+            _ if self.pending_ticks > 0 && now.is_at_or_after(self.next_tick) => {
+                let tick = self.next_tick;
+                self.pending_ticks -= 1;
+                self.next_tick = tick + self.tick_interval;
+                self.fall_at = Some(tick + self.tick_interval / 2);
+                (TriggerState::Rise, tick)
+            }
+            _ => (TriggerState::Unchanged, now),
+        }
+    }
+
+    /// Delay the edges of every second clock-trigger by the swing
+    fn apply_swing(
+        &mut self,
+        trigger_state: TriggerState,
+        timestamp: Instant,
+        now: Instant,
+    ) -> (TriggerState, Instant) {
+        if !self.external_swing || self.clock.applies_swing() {
+            return self
+                .pop_swung_edge(now)
+                .unwrap_or((trigger_state, timestamp));
+        }
+
+        let delayed = match trigger_state {
+            TriggerState::Rise => {
+                // A long delayed pulse must not cut the next one short
+                self.swung_edges.cancel(SwingTask::Fall);
+                let offbeat = self.offbeat;
+                self.offbeat = !offbeat;
+                self.swung_pulse = false;
+                match self.interval() {
+                    Some(interval) if offbeat && self.swing > 0 => {
+                        self.swing_delay = interval * self.swing as u32 / 100;
+                        self.swung_pulse = true;
+                        Some(SwingTask::Rise)
+                    }
+                    _ => None,
+                }
+            }
+            // Keep the width of a delayed pulse, even if its rise was already passed on
+            TriggerState::Fall if self.swung_pulse => {
+                self.swung_pulse = false;
+                Some(SwingTask::Fall)
+            }
+            _ => None,
+        };
+        if let Some(id) = delayed {
+            // There are at most two pending edges
+            let _ = self
+                .swung_edges
+                .push(Task::new(id, timestamp + self.swing_delay));
+        }
+
+        match self.pop_swung_edge(now) {
+            Some(edge) => {
+                // Pass on the current edge with the next check
+                if delayed.is_none() && trigger_state != TriggerState::Unchanged {
+                    let id = if trigger_state == TriggerState::Rise {
+                        SwingTask::Rise
+                    } else {
+                        SwingTask::Fall
+                    };
+                    let _ = self.swung_edges.push(Task::new(id, timestamp));
+                }
+                edge
+            }
+            None if delayed.is_some() => (TriggerState::Unchanged, now),
+            None => (trigger_state, timestamp),
+        }
+    }
+
+    fn pop_swung_edge(&mut self, now: Instant) -> Option<(TriggerState, Instant)> {
+        self.swung_edges.pop_due(now).map(|task| match task.id {
+            SwingTask::Rise => (TriggerState::Rise, task.timestamp),
+            SwingTask::Fall => (TriggerState::Fall, task.timestamp),
+        })
+    }
+}
+
+impl<CLOCK: ClockTrait> ClockTrait for RatioClock<CLOCK> {
+    fn check<S: uWrite<Error = Void>>(
+        &mut self,
+        now: Instant,
+        serial: &mut SerialWrapper<S>,
+        sequence: &Sequence,
+    ) -> ClockResult {
+        if self.rewind {
+            self.rewind = false;
+            self.playhead.start(sequence);
+        }
+        let inner = self.clock.check(now, serial, sequence);
+        if inner.trigger_state == TriggerState::Rise {
+            if let Some(last_inner_rise) = self.last_inner_rise {
+                self.estimator
+                    .update(inner.timestamp.duration_since(last_inner_rise));
+            }
+            self.last_inner_rise = Some(inner.timestamp);
+        }
+
+        let (trigger_state, timestamp) = match self.ratio {
+            ClockRatio::Divide(n) => (self.divide(&inner, n), inner.timestamp),
+            ClockRatio::Multiply(n) => self.multiply(&inner, n, now),
+        };
+        let (trigger_state, timestamp) = self.apply_swing(trigger_state, timestamp, now);
+        let mut wrapped = false;
+        if trigger_state == TriggerState::Rise {
+            if self.restart {
+                self.restart = false;
+                self.playhead.start(sequence);
+            } else {
+                wrapped = self.playhead.advance(sequence);
+            }
+        }
+
+        ClockResult {
+            trigger_state,
+            step_counter: self.playhead.step(),
+            wrapped,
+            timestamp,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.clock.reset();
+        self.rise_counter = 0;
+        // The direction of the sequence is only known with the next check
+        self.rewind = true;
+        self.restart = false;
+        self.offbeat = true;
+        self.swung_edges.clear()
+    }
+
+    fn restart(&mut self) {
+        self.reset();
+        self.clock.restart();
+        self.restart = true;
+        self.offbeat = false
+    }
+
+    fn start(&mut self, sequence: &Sequence) -> StepCounterType {
+        self.reset();
+        self.rewind = false;
+        self.playhead.start(sequence)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        let interval = self.inner_interval()?;
+        Some(match self.ratio {
+            ClockRatio::Divide(n) => interval * n as u32,
+            ClockRatio::Multiply(n) => interval / n as u32,
+        })
+    }
+
+    fn set_direction(&mut self, direction: Option<Direction>) {
+        self.playhead.set_direction(direction)
+    }
+
+    fn set_internal_interval(&mut self, interval: Duration) {
+        self.clock.set_internal_interval(interval)
+    }
+
+    fn set_swing(&mut self, swing: u8) {
+        self.swing = swing.min(MAX_SWING);
+        self.clock.set_swing(self.swing)
+    }
+
+    fn applies_swing(&self) -> bool {
+        self.external_swing || self.clock.applies_swing()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::{ExternalClock, InternalClock, PollingEdgeSource};
+    use crate::mock::{MockInputPin, MockSerial};
+    use crate::random::DEFAULT_SEED;
+    use crate::seq;
+
+    fn rises(ratio: ClockRatio) -> Vec<u32> {
+        let mut clock = RatioClock::new(
+            InternalClock::new(Duration::from_millis(100), Duration::from_millis(5)),
+            ratio,
+            DEFAULT_SEED,
+        );
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 15, 15, 15);
+        (0..=400)
+            .filter(|millis| {
+                let result = clock.check(Instant::from_millis(*millis), &mut serial, &sequence);
+                result.trigger_state == TriggerState::Rise
+            })
+            .collect()
+    }
+
+    #[test]
+    fn divides_and_multiplies_clock_triggers() {
+        assert_eq!(rises(ClockRatio::Divide(2)), [100, 300]);
+        assert_eq!(rises(ClockRatio::Multiply(1)), [100, 200, 300, 400]);
+        assert_eq!(
+            rises(ClockRatio::Multiply(4)),
+            [100, 125, 150, 175, 200, 225, 250, 275, 300, 325, 350, 375, 400]
+        );
+        assert_eq!("/3".parse(), Ok(ClockRatio::Divide(3)));
+        assert_eq!("x2".parse(), Ok(ClockRatio::Multiply(2)));
+        assert_eq!("x0".parse::<ClockRatio>(), Err(()));
+    }
+
+    #[test]
+    fn playhead_starts_and_wraps_in_the_direction_of_the_sequence() {
+        let mut clock = RatioClock::new(
+            InternalClock::new(Duration::from_millis(100), Duration::from_millis(5)),
+            ClockRatio::default(),
+            DEFAULT_SEED,
+        );
+        clock.set_direction(Some(Direction::Reverse));
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 15, 15, 15);
+        let mut play = |clock: &mut RatioClock<InternalClock>, from: u32| -> Vec<(usize, bool)> {
+            (from..from + 400)
+                .map(|millis| clock.check(Instant::from_millis(millis), &mut serial, &sequence))
+                .filter(|result| result.trigger_state == TriggerState::Rise)
+                .map(|result| (result.step_counter, result.wrapped))
+                .collect()
+        };
+
+        let steps = [(2, false), (1, false), (0, false), (3, true)];
+        assert_eq!(play(&mut clock, 1), steps);
+        // A reset moves the playhead back to the last step
+        clock.reset();
+        assert_eq!(play(&mut clock, 401), steps);
+    }
+
+    #[test]
+    fn external_swing_delays_every_second_clock_trigger() {
+        let input = MockInputPin::new();
+        let mut clock = RatioClock::new(
+            ExternalClock::new(PollingEdgeSource::new(input.clone())),
+            ClockRatio::default(),
+            DEFAULT_SEED,
+        );
+        clock.set_external_swing(true);
+        clock.set_swing(50);
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 15, 15, 15);
+
+        let rises: Vec<u32> = (0..=450)
+            .filter(|millis| {
+                input.set(millis % 100 < 10);
+                let result = clock.check(Instant::from_millis(*millis), &mut serial, &sequence);
+                result.trigger_state == TriggerState::Rise
+            })
+            .collect();
+        // The first clock-trigger (second step) is not delayed, as the interval is still unknown
+        assert_eq!(rises, [0, 100, 250, 300, 450]);
+    }
+
+    #[test]
+    fn external_swing_keeps_the_width_of_a_pulse_longer_than_the_delay() {
+        let input = MockInputPin::new();
+        let mut clock = RatioClock::new(
+            ExternalClock::new(PollingEdgeSource::new(input.clone())),
+            ClockRatio::default(),
+            DEFAULT_SEED,
+        );
+        clock.set_external_swing(true);
+        clock.set_swing(20);
+        let mut serial = SerialWrapper::new(false, MockSerial::new());
+        let sequence = seq!(15, 15, 15, 15);
+
+        let mut rises = Vec::new();
+        let mut falls = Vec::new();
+        for millis in 0..=450 {
+            input.set(millis % 100 < 60);
+            let result = clock.check(Instant::from_millis(millis), &mut serial, &sequence);
+            match result.trigger_state {
+                TriggerState::Rise => rises.push(millis),
+                TriggerState::Fall => falls.push(millis),
+                TriggerState::Unchanged => {}
+            }
+        }
+        // The delayed pulse at 200 ms rises at 220 ms, before the input falls at 260 ms
+        assert_eq!(rises, [0, 100, 220, 300, 420]);
+        assert_eq!(falls, [60, 160, 280, 360]);
+    }
+}
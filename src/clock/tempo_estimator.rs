@@ -0,0 +1,113 @@
+use crate::time::Duration;
+
+/// Deviation from the estimate (in 1/8 of the interval) up to which an interval is accepted
+const JITTER_TOLERANCE_EIGHTHS: u32 = 2;
+/// Number of consecutive outliers after which they are accepted as the new tempo
+const OUTLIERS_FOR_TEMPO_CHANGE: u8 = 3;
+/// Weight of a new interval in the moving average is `1 / SMOOTHING`
+const SMOOTHING: u32 = 4;
+
+/// Estimate the interval between clock-triggers from measured intervals
+///
+/// Small deviations are smoothed with an exponential moving average. Intervals that deviate more
+/// than 25% are rejected as jitter (e.g. a missed or doubled edge) unless they repeat, in which
+/// case the tempo changed.
+#[derive(Copy, Clone, Default)]
+pub struct TempoEstimator {
+    estimate: Option<Duration>,
+    outliers: u8,
+    last_outlier: Option<Duration>,
+}
+
+impl TempoEstimator {
+    pub const fn new() -> Self {
+        Self {
+            estimate: None,
+            outliers: 0,
+            last_outlier: None,
+        }
+    }
+
+    /// Feed a measured interval into the estimator
+    pub fn update(&mut self, interval: Duration) {
+        let estimate = match self.estimate {
+            Some(estimate) => estimate,
+            None => {
+                self.estimate = Some(interval);
+                return;
+            }
+        };
+
+        if Self::is_close(estimate, interval) {
+            self.outliers = 0;
+            self.last_outlier = None;
+            let estimate = estimate.as_micros();
+            let interval = interval.as_micros();
+            // estimate + (interval - estimate) / SMOOTHING without leaving the unsigned range
+            let smoothed = (estimate * (SMOOTHING - 1) + interval) / SMOOTHING;
+            self.estimate = Some(Duration::from_micros(smoothed));
+            return;
+        }
+
+        // Only consistent outliers indicate a tempo change
+        match self.last_outlier {
+            Some(last_outlier) if Self::is_close(last_outlier, interval) => self.outliers += 1,
+            _ => self.outliers = 1,
+        }
+        self.last_outlier = Some(interval);
+        if self.outliers >= OUTLIERS_FOR_TEMPO_CHANGE {
+            self.estimate = Some(interval);
+            self.outliers = 0;
+            self.last_outlier = None;
+        }
+    }
+
+    /// Return the estimated interval between clock-triggers
+    pub fn estimate(&self) -> Option<Duration> {
+        self.estimate
+    }
+
+    /// Forget the estimate
+    pub fn reset(&mut self) {
+        *self = Self::new()
+    }
+
+    fn is_close(reference: Duration, interval: Duration) -> bool {
+        let reference = reference.as_micros();
+        let interval = interval.as_micros();
+        let deviation = interval.max(reference) - interval.min(reference);
+
+        deviation <= reference / 8 * JITTER_TOLERANCE_EIGHTHS
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(millis: u32) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn smooths_jitter_and_follows_tempo_changes() {
+        let mut estimator = TempoEstimator::new();
+        assert_eq!(estimator.estimate(), None);
+        estimator.update(ms(500));
+        estimator.update(ms(520));
+        assert_eq!(estimator.estimate(), Some(ms(505)));
+
+        // A single missed edge is rejected
+        estimator.update(ms(1000));
+        assert_eq!(estimator.estimate(), Some(ms(505)));
+        estimator.update(ms(505));
+        assert_eq!(estimator.estimate(), Some(ms(505)));
+
+        // A repeated new interval is a tempo change
+        estimator.update(ms(250));
+        estimator.update(ms(252));
+        assert_eq!(estimator.estimate(), Some(ms(505)));
+        estimator.update(ms(250));
+        assert_eq!(estimator.estimate(), Some(ms(250)));
+    }
+}
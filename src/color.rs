@@ -1,11 +1,11 @@
 use crate::dac_byte::DacByte;
 use crate::serial_wrapper::SerialWrapper;
 use crate::RGB_LED_COUNT;
-use arduino_uno::hal::port::mode::InputMode;
-use arduino_uno::prelude::*;
+use embedded_hal::serial::Read;
 use smart_leds::hsv::Hsv;
 use smart_leds::RGB8;
-use void::ResultVoidExt;
+use ufmt::uWrite;
+use void::{ResultVoidExt, Void};
 
 pub type Color = RGB8;
 
@@ -14,6 +14,8 @@ pub const COLOR_TRIGGER: Color = Color { r: 10, g: 0, b: 2 };
 pub const COLOR_NO_TRIGGER: Color = Color { r: 2, g: 0, b: 0 };
 pub const COLOR_CURRENT_TRIGGER: Color = Color { r: 4, g: 0, b: 40 };
 pub const COLOR_CURRENT_NO_TRIGGER: Color = Color { r: 2, g: 0, b: 10 };
+pub const COLOR_STOPPED: Color = Color { r: 8, g: 0, b: 0 };
+pub const COLOR_PAUSED: Color = Color { r: 8, g: 4, b: 0 };
 
 pub const BRIGHTNESS_DEFAULT: u8 = 3;
 pub const BRIGHTNESS_CURRENT_TRIGGER: u8 = 12;
@@ -94,8 +96,8 @@ pub const fn color_for_dac_byte(dac_byte: DacByte, saturation: u8, brightness: u
     })
 }
 
-pub fn color_from_serial<IMODE: InputMode>(
-    mut serial: &mut SerialWrapper<IMODE>,
+pub fn color_from_serial<S: uWrite<Error = Void> + Read<u8, Error = Void>>(
+    mut serial: &mut SerialWrapper<S>,
 ) -> Result<Color, ()> {
     ufmt::uwriteln!(&mut serial, "Start reading color\r",).void_unwrap();
 
@@ -140,8 +142,8 @@ fn u8_from_color_buffer(buffer: &[u8; 2]) -> u8 {
     }
 }
 
-fn collect_to_buffer_from_serial<IMODE: InputMode>(
-    mut serial: &mut SerialWrapper<IMODE>,
+fn collect_to_buffer_from_serial<S: uWrite<Error = Void> + Read<u8, Error = Void>>(
+    mut serial: &mut SerialWrapper<S>,
     buffer: &mut [u8; 2],
 ) -> Result<(), ()> {
     for i in 0..2 {
@@ -1,17 +1,16 @@
 use crate::dac_byte::DacByte;
-use arduino_uno::hal::port::mode::Output;
-use arduino_uno::hal::port::portc::{PC0, PC1, PC2, PC3};
-use arduino_uno::prelude::*;
-
-pub struct Dac {
-    a0: PC0<Output>,
-    a1: PC1<Output>,
-    a2: PC2<Output>,
-    a3: PC3<Output>,
+use embedded_hal::digital::v2::OutputPin;
+use void::{ResultVoidExt, Void};
+
+pub struct Dac<PIN: OutputPin<Error = Void>> {
+    a0: PIN,
+    a1: PIN,
+    a2: PIN,
+    a3: PIN,
 }
 
-impl Dac {
-    pub fn new(a0: PC0<Output>, a1: PC1<Output>, a2: PC2<Output>, a3: PC3<Output>) -> Self {
+impl<PIN: OutputPin<Error = Void>> Dac<PIN> {
+    pub fn new(a0: PIN, a1: PIN, a2: PIN, a3: PIN) -> Self {
         Self { a0, a1, a2, a3 }
     }
 
@@ -45,3 +44,11 @@ impl Dac {
         .void_unwrap();
     }
 }
+
+/// Voltage of a high output pin in millivolts
+pub const HIGH_LEVEL_MILLIVOLTS: u32 = 5000;
+
+/// Return the voltage in millivolts that the R-2R resistor ladder outputs for the given value
+pub const fn ladder_millivolts(input: DacByte) -> u32 {
+    HIGH_LEVEL_MILLIVOLTS * input.value() as u32 / 16
+}
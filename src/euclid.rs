@@ -0,0 +1,182 @@
+use crate::dac_byte::DacByte;
+use crate::sequence::{Sequence, MAX_STEPS};
+use core::str::FromStr;
+
+/// Generator for Euclidean rhythms, which spread `pulses` as evenly as possible over `steps`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Euclid {
+    steps: u8,
+    pulses: u8,
+    rotation: u8,
+    hit: u8,
+    rest: u8,
+}
+
+impl Euclid {
+    /// Create a generator for `pulses` hits within `steps` (1-`MAX_STEPS`)
+    pub const fn new(steps: u8, pulses: u8) -> Self {
+        Self {
+            steps,
+            pulses,
+            rotation: 0,
+            hit: 15,
+            rest: 0,
+        }
+    }
+
+    /// Return a copy of the generator that rotates the pattern `rotation` steps to the right
+    pub const fn with_rotation(mut self, rotation: u8) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Return a copy of the generator with the CV values for hits and rests
+    pub const fn with_values(mut self, hit: u8, rest: u8) -> Self {
+        self.hit = hit;
+        self.rest = rest;
+        self
+    }
+
+    /// Return the number of steps (clamped to 1-`MAX_STEPS`)
+    pub fn steps(&self) -> u8 {
+        self.len() as u8
+    }
+
+    pub fn pulses(&self) -> u8 {
+        self.pulses
+    }
+
+    /// Change the number of pulses (clamped to the number of steps)
+    pub fn set_pulses(&mut self, pulses: u8) {
+        self.pulses = pulses.min(self.len() as u8)
+    }
+
+    /// Return the generated sequence
+    pub fn sequence(&self) -> Sequence {
+        let len = self.len();
+        let pattern = self.pattern();
+        let mut steps = [DacByte::min(); MAX_STEPS];
+        for (i, step) in steps[..len].iter_mut().enumerate() {
+            let source = (i + len - self.rotation as usize % len) % len;
+            *step = DacByte::new(if pattern[source] { self.hit } else { self.rest });
+        }
+
+        Sequence::new(&steps[..len])
+    }
+
+    /// Return the hits of the unrotated pattern, which starts with a hit
+    pub fn pattern(&self) -> [bool; MAX_STEPS] {
+        let len = self.len();
+        let pulses = (self.pulses as usize).min(len);
+        let mut pattern = [false; MAX_STEPS];
+        if pulses == 0 || pulses == len {
+            for hit in pattern[..len].iter_mut() {
+                *hit = pulses > 0;
+            }
+            return pattern;
+        }
+
+        // Bjorklund's algorithm: the counts and remainders of the repeated division (like
+        // Euclid's algorithm for the greatest common divisor) describe the pattern
+        let mut counts = [0; MAX_STEPS];
+        let mut remainders = [0; MAX_STEPS];
+        remainders[0] = pulses;
+        let mut divisor = len - pulses;
+        let mut level = 0;
+        loop {
+            counts[level] = divisor / remainders[level];
+            remainders[level + 1] = divisor % remainders[level];
+            divisor = remainders[level];
+            level += 1;
+            if remainders[level] <= 1 {
+                break;
+            }
+        }
+        counts[level] = divisor;
+
+        let mut built = 0;
+        build(
+            level as isize,
+            &counts,
+            &remainders,
+            &mut pattern,
+            &mut built,
+        );
+
+        // Start with the first hit
+        let first_hit = pattern[..len].iter().position(|hit| *hit).unwrap_or(0);
+        pattern[..len].rotate_left(first_hit);
+        pattern
+    }
+
+    fn len(&self) -> usize {
+        (self.steps as usize).clamp(1, MAX_STEPS)
+    }
+}
+
+impl FromStr for Euclid {
+    type Err = ();
+
+    /// Parse `STEPS:PULSES` or `STEPS:PULSES:ROTATION`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':').map(|part| part.parse::<u8>().map_err(|_| ()));
+        let steps = parts.next().ok_or(())??;
+        let pulses = parts.next().ok_or(())??;
+        let rotation = parts.next().transpose()?.unwrap_or(0);
+        if parts.next().is_some() || steps == 0 || steps as usize > MAX_STEPS || pulses > steps {
+            return Err(());
+        }
+
+        Ok(Self::new(steps, pulses).with_rotation(rotation))
+    }
+}
+
+fn build(
+    level: isize,
+    counts: &[usize; MAX_STEPS],
+    remainders: &[usize; MAX_STEPS],
+    pattern: &mut [bool; MAX_STEPS],
+    built: &mut usize,
+) {
+    match level {
+        -1 => *built += 1,
+        -2 => {
+            pattern[*built] = true;
+            *built += 1
+        }
+        _ => {
+            for _ in 0..counts[level as usize] {
+                build(level - 1, counts, remainders, pattern, built);
+            }
+            if remainders[level as usize] != 0 {
+                build(level - 2, counts, remainders, pattern, built);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pattern(euclid: Euclid) -> String {
+        let sequence = euclid.sequence();
+        (0..sequence.len())
+            .map(|i| if sequence.matches(i) { 'x' } else { '.' })
+            .collect()
+    }
+
+    #[test]
+    fn spreads_pulses_evenly() {
+        assert_eq!(pattern(Euclid::new(8, 3)), "x..x..x.");
+        assert_eq!(pattern(Euclid::new(8, 5)), "x.xx.xx.");
+        assert_eq!(pattern(Euclid::new(13, 5)), "x..x.x..x.x..");
+        assert_eq!(pattern(Euclid::new(8, 3).with_rotation(1)), ".x..x..x");
+        assert_eq!(pattern(Euclid::new(4, 0)), "....");
+        assert_eq!(pattern(Euclid::new(4, 4)), "xxxx");
+
+        let sequence = Euclid::new(4, 1).with_values(12, 3).sequence();
+        assert_eq!(sequence.get_step(0).map(|step| step.value()), Some(12));
+        assert_eq!(sequence.get_step(1).map(|step| step.value()), Some(3));
+    }
+}
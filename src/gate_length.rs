@@ -0,0 +1,35 @@
+use crate::time::Duration;
+use ufmt::{derive::uDebug, uDebug, uDisplay, uWrite, Formatter};
+
+/// Duration of the gate of a single step
+#[derive(Copy, Clone, PartialEq, uDebug)]
+pub enum GateLength {
+    /// The gate is controlled by the `TriggerMode`
+    TriggerMode,
+    /// Hold the gate for the given percentage (1-100) of the clock period
+    Percent(u8),
+    /// Hold the gate until the next step (legato if the next step triggers too)
+    Tie,
+}
+
+impl GateLength {
+    /// Return the gate duration for the given clock period
+    ///
+    /// Returns `None` if the duration is not bound to the clock period
+    pub fn duration(&self, period: Duration) -> Option<Duration> {
+        match self {
+            GateLength::Percent(percent) => Some(period * (*percent).min(100) as u32 / 100),
+            GateLength::TriggerMode | GateLength::Tie => None,
+        }
+    }
+}
+
+impl uDisplay for GateLength {
+    #[inline(always)]
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        <GateLength as uDebug>::fmt(self, f)
+    }
+}
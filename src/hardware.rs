@@ -0,0 +1,35 @@
+use crate::clock::EdgeSource;
+use crate::time::Instant;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::Read;
+use smart_leds::{SmartLedsWrite, RGB8};
+use ufmt::uWrite;
+use void::Void;
+
+/// Source of the current time
+pub trait TimeSource {
+    /// Return the current value of the microsecond counter
+    fn now(&self) -> Instant;
+}
+
+/// Analog input connected to the ADC
+pub trait AnalogInput {
+    /// Read the current value from the ADC (blocking)
+    fn read(&mut self) -> u16;
+}
+
+/// Collection of the peripheral types the sequencer is built upon
+///
+/// The firmware implements this for the Arduino Uno (`board::ArduinoUno`), while tests and host
+/// tools use the mocked peripherals from `mock::MockHardware`.
+pub trait Hardware {
+    type OutputPin: OutputPin<Error = Void>;
+    type ClockInput: EdgeSource;
+    type ResetInput: EdgeSource;
+    type RunInput: EdgeSource;
+    type ButtonInput: InputPin<Error = Void>;
+    type Leds: SmartLedsWrite<Color = RGB8>;
+    type Serial: uWrite<Error = Void> + Read<u8, Error = Void>;
+    type TimeSource: TimeSource;
+    type AnalogInput: AnalogInput;
+}
@@ -0,0 +1,72 @@
+use crate::board::ClockInputPin;
+use crate::clock::{ClockEdge, ClockEdgeQueue, EdgeSource};
+use crate::millis::micros;
+use crate::time::Instant;
+use core::cell;
+use embedded_hal::digital::v2::InputPin;
+use void::ResultVoidExt;
+
+/// Edges captured by the `INT0` interrupt
+static EDGES: avr_device::interrupt::Mutex<cell::RefCell<ClockEdgeQueue>> =
+    avr_device::interrupt::Mutex::new(cell::RefCell::new(ClockEdgeQueue::new()));
+
+/// Clock input on `PD2` whose edges are captured by the `INT0` interrupt
+///
+/// The edges are timestamped in the interrupt handler, so they are neither delayed nor lost
+/// while the main loop is busy. Edges that do not fit into the queue are counted in `dropped()`.
+pub struct Int0EdgeSource {
+    // Keep the pin configured as input as long as the interrupt is in use
+    _input: ClockInputPin,
+}
+
+impl Int0EdgeSource {
+    /// Enable the `INT0` interrupt for the next edge of the clock input
+    pub fn new(exint: arduino_uno::pac::EXINT, input: ClockInputPin) -> Self {
+        // Wait for the edge that leaves the current level
+        let sense = if input.is_high().void_unwrap() {
+            SENSE_FALLING
+        } else {
+            SENSE_RISING
+        };
+        exint.eicra.modify(|_, w| w.isc0().bits(sense));
+        exint.eimsk.modify(|_, w| w.int0().set_bit());
+
+        Self { _input: input }
+    }
+}
+
+impl EdgeSource for Int0EdgeSource {
+    fn next_edge(&mut self, _now: Instant) -> Option<ClockEdge> {
+        avr_device::interrupt::free(|cs| EDGES.borrow(cs).borrow_mut().pop())
+    }
+
+    fn dropped(&self) -> u16 {
+        avr_device::interrupt::free(|cs| EDGES.borrow(cs).borrow().dropped())
+    }
+}
+
+/// ISC0 = 0b11: The rising edge of INT0 generates an interrupt request
+const SENSE_RISING: u8 = 0b11;
+/// ISC0 = 0b10: The falling edge of INT0 generates an interrupt request
+const SENSE_FALLING: u8 = 0b10;
+
+#[avr_device::interrupt(atmega328p)]
+fn INT0() {
+    let timestamp = Instant::from_micros(micros());
+    // The sense mode tells which edge fired, then the opposite edge is armed. Reading `PD2` instead
+    // would misclassify pulses that are already over when the handler runs.
+    // Safe because only `new()` and the interrupt handler change `EICRA`
+    let exint = unsafe { &*arduino_uno::pac::EXINT::ptr() };
+    let rising = exint.eicra.read().isc0().bits() == SENSE_RISING;
+    let next_sense = if rising { SENSE_FALLING } else { SENSE_RISING };
+    exint.eicra.modify(|_, w| w.isc0().bits(next_sense));
+    // Changing the sense mode can raise the interrupt flag without an edge
+    exint.eifr.write(|w| w.intf0().set_bit());
+
+    avr_device::interrupt::free(|cs| {
+        EDGES
+            .borrow(cs)
+            .borrow_mut()
+            .push(ClockEdge { rising, timestamp })
+    })
+}
@@ -1,40 +1,28 @@
 use crate::color::{
-    color_for_dac_byte, BRIGHTNESS_CURRENT_TRIGGER, BRIGHTNESS_CURRENT_NO_TRIGGER, BRIGHTNESS_DEFAULT,
-    COLOR_UNMAPPED,
+    color_for_dac_byte, BRIGHTNESS_CURRENT_NO_TRIGGER, BRIGHTNESS_CURRENT_TRIGGER,
+    BRIGHTNESS_DEFAULT, COLOR_PAUSED, COLOR_STOPPED, COLOR_UNMAPPED,
 };
 use crate::sequence::Sequence;
-use crate::ws2812::prerendered::Ws2812;
+use crate::transport::TransportState;
 use crate::RGB_LED_COUNT;
-use arduino_uno::hal::port::mode::PullUp;
-use arduino_uno::spi::Spi;
 use smart_leds::{SmartLedsWrite, RGB8};
 
-static mut OUTPUT_BUFFER: [u8; 136] = [0; 40 + (RGB_LED_COUNT * 12)];
-
-pub struct LedController<'a> {
-    outlet: Ws2812<'a, Spi<PullUp>>,
+pub struct LedController<LEDS: SmartLedsWrite<Color = RGB8>> {
+    outlet: LEDS,
     last_data: [RGB8; RGB_LED_COUNT],
-    _buffer: [u8; 40 + (RGB_LED_COUNT * 12)],
 }
 
-impl<'a> LedController<'a> {
-    pub fn new(spi: Spi<PullUp>) -> Self {
+impl<LEDS: SmartLedsWrite<Color = RGB8>> LedController<LEDS> {
+    pub fn new(outlet: LEDS) -> Self {
         let data: [RGB8; RGB_LED_COUNT] = [RGB8::default(); RGB_LED_COUNT];
-        let outlet = unsafe { Ws2812::new(spi, &mut OUTPUT_BUFFER) };
         Self {
             outlet,
             last_data: data,
-            _buffer: [0; 40 + (RGB_LED_COUNT * 12)],
         }
     }
 
-    // fn init_outlet(&mut self, spi: Spi<PullUp>) {
-    //     let ws2812 = unsafe { Ws2812::new(spi, &mut OUTPUT_BUFFER_ST) };
-    //     self.outlet = Some(ws2812);
-    // }
-
-    pub fn show_sequence(&mut self, sequence: Sequence) {
-        self.write(self.data_for_sequence(sequence)).unwrap();
+    pub fn show_sequence(&mut self, sequence: &Sequence) {
+        self.write(self.data_for_sequence(sequence, 0)).unwrap();
         //
         // let mut data: [RGB8; RGB_LED_COUNT] = [RGB8 { r: 0, g: 0, b: 0 }; RGB_LED_COUNT];
         //
@@ -52,24 +40,50 @@ impl<'a> LedController<'a> {
         // self.write(data).unwrap();
     }
 
+    /// Show the queued sequence until the switch takes effect (see `show_step()`)
+    pub fn show_pending(&mut self, pending: &Sequence) {
+        self.write(self.data_for_sequence(pending, 0)).unwrap();
+    }
+
+    /// Show the transport state: red while stopped, every other LED amber while paused
+    pub fn show_transport(&mut self, state: TransportState, sequence: &Sequence) {
+        let data = match state {
+            TransportState::Running => self.data_for_sequence(sequence, 0),
+            TransportState::Stopped => [COLOR_STOPPED; RGB_LED_COUNT],
+            TransportState::Paused => {
+                let mut data = [COLOR_UNMAPPED; RGB_LED_COUNT];
+                for color in data.iter_mut().step_by(2) {
+                    *color = COLOR_PAUSED;
+                }
+                data
+            }
+        };
+        self.write(data).unwrap();
+    }
+
+    /// Show the sequence with the current step highlighted
+    ///
+    /// If a `pending` sequence is queued, it is shown instead of `sequence` (with the current step
+    /// on top) until the switch takes effect.
     pub fn show_step(
         &mut self,
-        sequence: Sequence,
+        sequence: &Sequence,
         step_counter: usize,
         sequence_matches: bool,
+        pending: Option<&Sequence>,
     ) -> Result<(), ()> {
-        let mut data = self.data_for_sequence(sequence);
+        // Sequences longer than the LED strip are displayed in pages of `RGB_LED_COUNT` steps
+        let page_offset = step_counter - step_counter % RGB_LED_COUNT;
+        let mut data = self.data_for_sequence(pending.unwrap_or(sequence), page_offset);
 
-        let step_pointer: u8 = 0b00000001 << step_counter;
-        match sequence.get_step(step_pointer) {
+        let led = step_counter - page_offset;
+        match sequence.get_step(step_counter) {
             None => {}
             Some(dac_byte) => {
                 if sequence_matches {
-                    data[step_counter] =
-                        color_for_dac_byte(dac_byte, 255, BRIGHTNESS_CURRENT_TRIGGER);
+                    data[led] = color_for_dac_byte(dac_byte, 255, BRIGHTNESS_CURRENT_TRIGGER);
                 } else {
-                    data[step_counter] =
-                        color_for_dac_byte(dac_byte, 255, BRIGHTNESS_CURRENT_NO_TRIGGER);
+                    data[led] = color_for_dac_byte(dac_byte, 255, BRIGHTNESS_CURRENT_NO_TRIGGER);
                 }
             }
         };
@@ -90,13 +104,13 @@ impl<'a> LedController<'a> {
         }
     }
 
-    pub fn data_for_sequence(&self, sequence: Sequence) -> [RGB8; RGB_LED_COUNT] {
+    /// Return the colors for the `RGB_LED_COUNT` steps starting at index `offset`
+    pub fn data_for_sequence(&self, sequence: &Sequence, offset: usize) -> [RGB8; RGB_LED_COUNT] {
         let mut data: [RGB8; RGB_LED_COUNT] = [COLOR_UNMAPPED; RGB_LED_COUNT];
 
-        for step_counter in 0..RGB_LED_COUNT {
-            let step_pointer: u8 = 0b00000001 << step_counter;
-            if let Some(dac_byte) = sequence.get_step(step_pointer) {
-                data[step_counter] = color_for_dac_byte(dac_byte, 255, BRIGHTNESS_DEFAULT);
+        for (led, color) in data.iter_mut().enumerate() {
+            if let Some(dac_byte) = sequence.get_step(offset + led) {
+                *color = color_for_dac_byte(dac_byte, 255, BRIGHTNESS_DEFAULT);
             }
         }
         data
@@ -0,0 +1,105 @@
+#![cfg_attr(target_arch = "avr", feature(const_panic))]
+#![cfg_attr(target_arch = "avr", feature(abi_avr_interrupt))]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+pub mod app;
+#[cfg(target_arch = "avr")]
+pub mod board;
+pub mod clock;
+pub mod color;
+pub mod dac;
+pub mod dac_byte;
+pub mod euclid;
+pub mod gate_length;
+pub mod hardware;
+#[cfg(target_arch = "avr")]
+pub mod int0;
+pub mod led_controller;
+#[cfg(target_arch = "avr")]
+pub mod millis;
+#[cfg(any(test, feature = "std"))]
+pub mod mock;
+pub mod playhead;
+pub mod random;
+pub mod scheduler;
+pub mod sequence;
+pub mod sequence_controller;
+pub mod serial_wrapper;
+#[cfg(any(test, feature = "std"))]
+pub mod simulator;
+pub mod tap_tempo;
+pub mod tempo_pot;
+pub mod time;
+pub mod transport;
+pub mod trig_condition;
+pub mod trigger;
+pub mod trigger_state;
+pub mod turing_machine;
+#[cfg(any(test, feature = "std"))]
+pub mod vcd;
+#[cfg(any(test, feature = "std"))]
+pub mod wav;
+
+// The Turing machine reads its probability from the pot, which the reset input replaces on `A4`
+#[cfg(all(
+    target_arch = "avr",
+    feature = "reset_input",
+    feature = "turing_machine"
+))]
+compile_error!("The `reset_input` and `turing_machine` features both need pin A4");
+
+use crate::clock::{ClockMode, ClockRatio};
+use crate::dac_byte::DacByte;
+use crate::euclid::Euclid;
+use crate::sequence::Sequence;
+use crate::sequence_controller::{SongEntry, SwitchMode};
+use crate::time::Duration;
+
+pub const DELAY_TIME: Duration = Duration::from_millis(5);
+pub const STEP_LED_COUNT: usize = 5;
+pub const RGB_LED_COUNT: usize = 8;
+
+pub const CLOCK_MODE: ClockMode = if cfg!(feature = "auto_trigger") {
+    ClockMode::Follow
+} else {
+    ClockMode::Mixed
+};
+/// Interval of the internal clock until the tempo is changed
+pub const INTERNAL_CLOCK_INTERVAL: Duration = Duration::from_millis(250);
+/// Duration without external clock edges after which `ClockMode::Mixed` uses the internal clock
+pub const EXTERNAL_CLOCK_TIMEOUT: Duration = Duration::from_millis(2000);
+/// Maximum delay of a reset after a clock-trigger for both to count as simultaneous
+pub const RESET_WINDOW: Duration = Duration::from_millis(5);
+/// Largest swing in percent of the clock interval
+pub const MAX_SWING: u8 = 75;
+/// Ratio between the clock-triggers and the steps of the sequence
+pub const CLOCK_RATIO: ClockRatio = ClockRatio::Multiply(1);
+/// Play Euclidean rhythms instead of `SEQUENCES` (the button cycles through the pulse counts)
+pub const EUCLID: Option<Euclid> = None;
+/// Play the entries of the song one after the other instead of looping the selected sequence
+pub const SONG: Option<&[SongEntry]> = None;
+// Reject a `SONG` that cannot be played when building the firmware
+#[cfg(target_arch = "avr")]
+const _: () = match SONG {
+    Some(song) if !sequence_controller::is_valid_song(song, SEQUENCES.len()) => {
+        panic!("SONG is not valid")
+    }
+    _ => (),
+};
+/// When a sequence change from the button takes effect
+pub const SWITCH_MODE: SwitchMode = SwitchMode::Immediate;
+
+pub const SEQUENCES: [Sequence; 12] = [
+    seq!(1, 3, 5, 8, 9, 10, 12, 15),
+    seq!(15, 5, 5, 5, 0),
+    seq!(0, 7, 15, 7, 0, 7, 15, 7,),    // 0b0½1½0½1½
+    seq!(15, 15, 15, 15, 15, 15, 15,),  // 0b11111111
+    seq!(0, 15, 0, 0, 15, 0, 0, 15,),   // 0b01001001
+    seq!(0, 15, 0, 15, 0, 15, 0, 15,),  // 0b01010101
+    seq!(0, 0, 0, 0, 15, 15, 15, 15,),  // 0b00001111
+    seq!(15, 15, 15, 15, 0, 0, 0, 0,),  // 0b11110000
+    seq!(15, 15, 0, 0, 15, 15, 0, 0,),  // 0b11001100
+    seq!(15, 15, 15, 0, 0, 15, 0, 15,), // 0b11100101
+    seq!(8, 8, 8, 12, 0, 8, 8, 12),
+    seq!(0, 0, 0, 0, 0, 0, 0, 0),
+];
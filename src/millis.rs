@@ -1,31 +1,79 @@
 use core::cell;
 // Example from https://github.com/Rahix/avr-hal/blob/master/boards/arduino-uno/examples/uno-millis.rs
 
-// Possible Values:
-//
-// ╔═══════════╦══════════════╦═══════════════════╗
-// ║ PRESCALER ║ TIMER_COUNTS ║ Overflow Interval ║
-// ╠═══════════╬══════════════╬═══════════════════╣
-// ║        64 ║          250 ║              1 ms ║
-// ║       256 ║          125 ║              2 ms ║
-// ║       256 ║          250 ║              4 ms ║
-// ║      1024 ║          125 ║              8 ms ║
-// ║      1024 ║          250 ║             16 ms ║
-// ╚═══════════╩══════════════╩═══════════════════╝
-const PRESCALER: u32 = 1024;
-const TIMER_COUNTS: u32 = 125;
-
-const MILLIS_INCREMENT: u32 = PRESCALER * TIMER_COUNTS / 16000;
-
-static MILLIS_COUNTER: avr_device::interrupt::Mutex<cell::Cell<u32>> =
+/// Configuration of TC0 (running at 16 MHz) for the time base
+///
+/// Possible Values:
+///
+/// ╔════════════════╦═══════════╦══════════════╦═══════════════════╦════════════╗
+/// ║ Configuration  ║ PRESCALER ║ TIMER_COUNTS ║ Overflow Interval ║ Resolution ║
+/// ╠════════════════╬═══════════╬══════════════╬═══════════════════╬════════════╣
+/// ║ OVERFLOW_1MS   ║        64 ║          250 ║              1 ms ║       4 µs ║
+/// ║ OVERFLOW_2MS   ║       256 ║          125 ║              2 ms ║      16 µs ║
+/// ║ OVERFLOW_4MS   ║       256 ║          250 ║              4 ms ║      16 µs ║
+/// ║ OVERFLOW_8MS   ║      1024 ║          125 ║              8 ms ║      64 µs ║
+/// ║ OVERFLOW_16MS  ║      1024 ║          250 ║             16 ms ║      64 µs ║
+/// ╚════════════════╩═══════════╩══════════════╩═══════════════════╩════════════╝
+///
+/// Fewer overflows mean fewer interrupts; the resolution of `micros()` only depends on the
+/// prescaler, because it adds the live `TCNT0` count to the overflow counter. See `TIMER_CONFIG`
+/// for the trade-off.
+#[derive(Copy, Clone)]
+pub struct TimerConfig {
+    prescaler: u32,
+    timer_counts: u32,
+}
+
+impl TimerConfig {
+    pub const OVERFLOW_1MS: TimerConfig = TimerConfig::new(64, 250);
+    pub const OVERFLOW_2MS: TimerConfig = TimerConfig::new(256, 125);
+    pub const OVERFLOW_4MS: TimerConfig = TimerConfig::new(256, 250);
+    pub const OVERFLOW_8MS: TimerConfig = TimerConfig::new(1024, 125);
+    pub const OVERFLOW_16MS: TimerConfig = TimerConfig::new(1024, 250);
+
+    const fn new(prescaler: u32, timer_counts: u32) -> Self {
+        Self {
+            prescaler,
+            timer_counts,
+        }
+    }
+
+    /// Microseconds per timer count
+    const fn micros_per_count(&self) -> u32 {
+        self.prescaler / 16
+    }
+
+    /// Microseconds between two overflow interrupts
+    const fn micros_per_overflow(&self) -> u32 {
+        self.micros_per_count() * self.timer_counts
+    }
+}
+
+/// Timer configuration used by the firmware
+///
+/// `OVERFLOW_1MS` is the only configuration with a sub-millisecond granularity worth having: it
+/// has the finest prescaler, so `micros()` (and every `Instant` of the clocks, the triggers and
+/// the INT0 edges) advances in 4 µs steps. The price is an overflow interrupt every millisecond,
+/// i.e. 1000 interrupts per second that delay other interrupts by a few µs each. The other
+/// configurations cut the interrupt rate by 2-16 times, but `micros()` then only advances in
+/// 16 µs or 64 µs steps.
+pub const TIMER_CONFIG: TimerConfig = TimerConfig::OVERFLOW_1MS;
+
+static MICROS_COUNTER: avr_device::interrupt::Mutex<cell::Cell<u32>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(0));
+static MICROS_PER_OVERFLOW: avr_device::interrupt::Mutex<cell::Cell<u32>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(0));
+static MICROS_PER_COUNT: avr_device::interrupt::Mutex<cell::Cell<u32>> =
     avr_device::interrupt::Mutex::new(cell::Cell::new(0));
 
-pub fn millis_init(tc0: arduino_uno::pac::TC0) {
+pub fn millis_init(tc0: arduino_uno::pac::TC0, config: TimerConfig) {
     // Configure the timer for the above interval (in CTC mode)
     // and enable its interrupt.
     tc0.tccr0a.write(|w| w.wgm0().ctc());
-    tc0.ocr0a.write(|w| unsafe { w.bits(TIMER_COUNTS as u8) });
-    tc0.tccr0b.write(|w| match PRESCALER {
+    // The counter includes `OCR0A`, so it overflows after `timer_counts` counts
+    tc0.ocr0a
+        .write(|w| unsafe { w.bits((config.timer_counts - 1) as u8) });
+    tc0.tccr0b.write(|w| match config.prescaler {
         8 => w.cs0().prescale_8(),
         64 => w.cs0().prescale_64(),
         256 => w.cs0().prescale_256(),
@@ -34,21 +82,44 @@ pub fn millis_init(tc0: arduino_uno::pac::TC0) {
     });
     tc0.timsk0.write(|w| w.ocie0a().set_bit());
 
-    // Reset the global millisecond counter
+    // Reset the global microsecond counter
     avr_device::interrupt::free(|cs| {
-        MILLIS_COUNTER.borrow(cs).set(0);
+        MICROS_COUNTER.borrow(cs).set(0);
+        MICROS_PER_OVERFLOW
+            .borrow(cs)
+            .set(config.micros_per_overflow());
+        MICROS_PER_COUNT.borrow(cs).set(config.micros_per_count());
     });
 }
 
 #[avr_device::interrupt(atmega328p)]
 fn TIMER0_COMPA() {
     avr_device::interrupt::free(|cs| {
-        let counter_cell = MILLIS_COUNTER.borrow(cs);
+        let counter_cell = MICROS_COUNTER.borrow(cs);
         let counter = counter_cell.get();
-        counter_cell.set(counter + MILLIS_INCREMENT);
+        counter_cell.set(counter.wrapping_add(MICROS_PER_OVERFLOW.borrow(cs).get()));
+    })
+}
+
+/// Return the microseconds since `millis_init()` (wraps after ~71 minutes)
+///
+/// Combines the overflow counter with the live value of `TCNT0`
+pub fn micros() -> u32 {
+    avr_device::interrupt::free(|cs| {
+        // Safe because the registers are only read
+        let tc0 = unsafe { &*arduino_uno::pac::TC0::ptr() };
+        let mut counter = MICROS_COUNTER.borrow(cs).get();
+        let count = tc0.tcnt0.read().bits() as u32;
+        // The overflow happened, but the interrupt has not been handled yet
+        if tc0.tifr0.read().ocf0a().bit_is_set() && count < tc0.ocr0a.read().bits() as u32 {
+            counter = counter.wrapping_add(MICROS_PER_OVERFLOW.borrow(cs).get());
+        }
+
+        counter.wrapping_add(count * MICROS_PER_COUNT.borrow(cs).get())
     })
 }
 
+/// Return the milliseconds since `millis_init()` (wraps together with `micros()`)
 pub fn millis() -> u32 {
-    avr_device::interrupt::free(|cs| MILLIS_COUNTER.borrow(cs).get())
+    micros() / 1000
 }
@@ -0,0 +1,330 @@
+//! Mocked peripherals to run the sequencer on the host
+//!
+//! Every mock shares its state through an `Rc`, so a clone can be kept outside of the `App` to
+//! inspect or drive the peripheral.
+use crate::app::App;
+use crate::clock::{Clock, ClockFactory, PollingEdgeSource, RatioClock};
+use crate::dac::Dac;
+use crate::euclid::Euclid;
+use crate::hardware::{AnalogInput, Hardware, TimeSource};
+use crate::led_controller::LedController;
+use crate::sequence::Sequence;
+use crate::sequence_controller::SequenceController;
+use crate::serial_wrapper::SerialWrapper;
+use crate::time::Instant;
+use crate::trigger::TriggerFactory;
+use crate::{RGB_LED_COUNT, SEQUENCES, STEP_LED_COUNT};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::Read;
+use smart_leds::{SmartLedsWrite, RGB8};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use ufmt::uWrite;
+use void::Void;
+
+pub struct MockHardware {}
+
+impl Hardware for MockHardware {
+    type OutputPin = MockOutputPin;
+    type ClockInput = MockClockInput;
+    type ResetInput = PollingEdgeSource<MockInputPin>;
+    type RunInput = PollingEdgeSource<MockInputPin>;
+    type ButtonInput = MockInputPin;
+    type Leds = MockLeds;
+    type Serial = MockSerial;
+    type TimeSource = MockTimeSource;
+    type AnalogInput = MockAnalogInput;
+}
+
+/// The mocked clock input is polled like a regular pin
+pub type MockClockInput = PollingEdgeSource<MockInputPin>;
+pub type MockClock = RatioClock<Clock<MockClockInput>>;
+
+pub type MockApp = App<MockHardware, MockClock>;
+
+/// Handles to all the peripherals of a mocked `App`
+#[derive(Clone, Default)]
+pub struct MockPeripherals {
+    pub step_output_pins: [MockOutputPin; STEP_LED_COUNT],
+    /// DAC bits from the least to the most significant (`A0` to `A3`)
+    pub dac_pins: [MockOutputPin; 4],
+    pub sequence_change_output: MockOutputPin,
+    pub trigger_output: MockOutputPin,
+    pub clock_input: MockInputPin,
+    pub reset_input: MockInputPin,
+    /// The run/stop gate input is high while the transport is running
+    pub run_input: MockInputPin,
+    /// The button is connected to a pull-up input, which means it is pressed while the pin is low
+    pub sequence_change_input: MockInputPin,
+    pub leds: MockLeds,
+    pub serial: MockSerial,
+    pub time_source: MockTimeSource,
+    /// Tempo pot (not connected unless set to `Some`)
+    pub analog_input: Option<MockAnalogInput>,
+}
+
+impl MockPeripherals {
+    pub fn new() -> Self {
+        let peripherals: Self = Default::default();
+        peripherals.sequence_change_input.set(true);
+        peripherals.run_input.set(true);
+        peripherals
+    }
+
+    /// Build an `App` that is wired to (clones of) these peripherals
+    pub fn build_app(
+        &self,
+        clock_factory: ClockFactory<MockClock>,
+        trigger_factory: TriggerFactory,
+    ) -> MockApp {
+        self.build_app_with_sequences(clock_factory, trigger_factory, &SEQUENCES)
+    }
+
+    /// Build an `App` that plays the given sequences instead of `SEQUENCES`
+    pub fn build_app_with_sequences(
+        &self,
+        clock_factory: ClockFactory<MockClock>,
+        trigger_factory: TriggerFactory,
+        sequences: &'static [Sequence],
+    ) -> MockApp {
+        let sequence_change_input = self.sequence_change_input.clone();
+        self.build_app_with_sequence_controller(
+            clock_factory,
+            trigger_factory,
+            SequenceController::with_sequences(sequence_change_input, sequences),
+        )
+    }
+
+    /// Build an `App` that plays the Euclidean rhythms of `euclid` instead of `SEQUENCES`
+    pub fn build_app_with_euclid(
+        &self,
+        clock_factory: ClockFactory<MockClock>,
+        trigger_factory: TriggerFactory,
+        euclid: Euclid,
+    ) -> MockApp {
+        let sequence_change_input = self.sequence_change_input.clone();
+        self.build_app_with_sequence_controller(
+            clock_factory,
+            trigger_factory,
+            SequenceController::with_euclid(sequence_change_input, euclid),
+        )
+    }
+
+    /// Build an `App` with the given sequence controller (e.g. to play a song)
+    pub fn build_app_with_sequence_controller(
+        &self,
+        clock_factory: ClockFactory<MockClock>,
+        trigger_factory: TriggerFactory,
+        sequence_controller: SequenceController<MockInputPin>,
+    ) -> MockApp {
+        let [a0, a1, a2, a3] = self.dac_pins.clone();
+
+        App::new(
+            self.step_output_pins.clone(),
+            Dac::new(a0, a1, a2, a3),
+            self.sequence_change_output.clone(),
+            SerialWrapper::new(true, self.serial.clone()),
+            trigger_factory.build(self.trigger_output.clone()),
+            clock_factory.build(PollingEdgeSource::new(self.clock_input.clone())),
+            Some(PollingEdgeSource::new(self.reset_input.clone())),
+            Some(PollingEdgeSource::new(self.run_input.clone())),
+            sequence_controller,
+            LedController::new(self.leds.clone()),
+            self.analog_input.clone(),
+            self.time_source.clone(),
+        )
+    }
+
+    /// Return the value currently set on the DAC pins
+    pub fn dac_value(&self) -> u8 {
+        self.dac_pins
+            .iter()
+            .enumerate()
+            .fold(0, |value, (bit, pin)| {
+                value | ((pin.is_high() as u8) << bit)
+            })
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockOutputPin {
+    state: Rc<Cell<bool>>,
+}
+
+impl MockOutputPin {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.state.get()
+    }
+}
+
+impl OutputPin for MockOutputPin {
+    type Error = Void;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.state.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.state.set(true);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockInputPin {
+    state: Rc<Cell<bool>>,
+}
+
+impl MockInputPin {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set(&self, high: bool) {
+        self.state.set(high)
+    }
+}
+
+impl InputPin for MockInputPin {
+    type Error = Void;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.state.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.state.get())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockLeds {
+    data: Rc<RefCell<[RGB8; RGB_LED_COUNT]>>,
+}
+
+impl MockLeds {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Return the colors of the last write
+    pub fn data(&self) -> [RGB8; RGB_LED_COUNT] {
+        *self.data.borrow()
+    }
+}
+
+impl SmartLedsWrite for MockLeds {
+    type Error = Void;
+    type Color = RGB8;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: Iterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let mut data = self.data.borrow_mut();
+        for (led, color) in data.iter_mut().zip(iterator) {
+            *led = color.into();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockSerial {
+    output: Rc<RefCell<String>>,
+    input: Rc<RefCell<VecDeque<u8>>>,
+}
+
+impl MockSerial {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Remove and return everything written to the serial so far
+    pub fn take_output(&self) -> String {
+        self.output.replace(String::new())
+    }
+
+    /// Queue bytes to be read from the serial
+    pub fn push_input(&self, input: &[u8]) {
+        self.input.borrow_mut().extend(input)
+    }
+}
+
+impl uWrite for MockSerial {
+    type Error = Void;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.output.borrow_mut().push_str(s);
+        Ok(())
+    }
+}
+
+impl Read<u8> for MockSerial {
+    type Error = Void;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.input
+            .borrow_mut()
+            .pop_front()
+            .ok_or(nb::Error::WouldBlock)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockTimeSource {
+    micros: Rc<Cell<u32>>,
+}
+
+impl MockTimeSource {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set(&self, millis: u32) {
+        self.micros.set(millis.wrapping_mul(1000))
+    }
+
+    pub fn set_micros(&self, micros: u32) {
+        self.micros.set(micros)
+    }
+
+    pub fn advance(&self, millis: u32) {
+        self.micros
+            .set(self.micros.get().wrapping_add(millis.wrapping_mul(1000)))
+    }
+}
+
+impl TimeSource for MockTimeSource {
+    fn now(&self) -> Instant {
+        Instant::from_micros(self.micros.get())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockAnalogInput {
+    value: Rc<Cell<u16>>,
+}
+
+impl MockAnalogInput {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set(&self, value: u16) {
+        self.value.set(value)
+    }
+}
+
+impl AnalogInput for MockAnalogInput {
+    fn read(&mut self) -> u16 {
+        self.value.get()
+    }
+}
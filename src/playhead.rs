@@ -0,0 +1,246 @@
+use crate::clock::StepCounterType;
+use crate::random::Random;
+use crate::sequence::Sequence;
+use core::str::FromStr;
+use ufmt::derive::uDebug;
+
+/// Order in which the steps of a sequence are played
+#[derive(Copy, Clone, PartialEq, Debug, uDebug)]
+pub enum Direction {
+    Forward,
+    Reverse,
+    /// Forward and backward without repeating the first and last step (`0 1 2 3 2 1 0 1`)
+    PingPong,
+    /// Forward and backward repeating the first and last step (`0 1 2 3 3 2 1 0 0 1`)
+    PingPongRepeat,
+    /// Any step of the sequence
+    Random,
+    /// Random walk: one step forward (50%), one step back (25%) or the same step again (25%)
+    Brownian,
+}
+
+impl FromStr for Direction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forward" => Ok(Direction::Forward),
+            "reverse" => Ok(Direction::Reverse),
+            "pingpong" => Ok(Direction::PingPong),
+            "pingpong-repeat" => Ok(Direction::PingPongRepeat),
+            "random" => Ok(Direction::Random),
+            "brownian" => Ok(Direction::Brownian),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Position within a sequence that advances in the selected `Direction`
+///
+/// The direction set at runtime takes precedence over the direction of the sequence.
+pub struct Playhead {
+    step: StepCounterType,
+    direction: Option<Direction>,
+    /// `true` while a ping-pong moves backward
+    backward: bool,
+    /// Number of steps played in the current loop (`Direction::Random` and `Direction::Brownian`)
+    loop_steps: StepCounterType,
+    random: Random,
+}
+
+/// Mixed into the seed so the random directions do not follow other users of the same seed
+const SEED_MASK: u32 = 0x91a7_b3c5;
+
+impl Playhead {
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            step: 0,
+            direction: None,
+            backward: false,
+            loop_steps: 0,
+            random: Random::new(seed ^ SEED_MASK),
+        }
+    }
+
+    /// Return the current step
+    pub fn step(&self) -> StepCounterType {
+        self.step
+    }
+
+    /// Override the direction of the sequences (`None` uses the direction of each sequence)
+    pub fn set_direction(&mut self, direction: Option<Direction>) {
+        self.direction = direction
+    }
+
+    /// Return the direction used for `sequence`
+    pub fn direction(&self, sequence: &Sequence) -> Direction {
+        self.direction.unwrap_or_else(|| sequence.get_direction())
+    }
+
+    /// Move to the step the sequence starts with (the last one if played in reverse)
+    pub fn start(&mut self, sequence: &Sequence) -> StepCounterType {
+        self.step = match self.direction(sequence) {
+            Direction::Reverse => sequence.len() - 1,
+            _ => 0,
+        };
+        self.backward = false;
+        self.loop_steps = 0;
+        self.step
+    }
+
+    /// Move to the next step and return `true` if the playhead wrapped around to the start of
+    /// the loop
+    ///
+    /// A loop ends where the direction brings the playhead back to the step it started with. The
+    /// random directions have no such step, their loops end after as many steps as the sequence
+    /// has.
+    pub fn advance(&mut self, sequence: &Sequence) -> bool {
+        let last = sequence.len() as StepCounterType - 1;
+        // The sequence may have been replaced by a shorter one
+        let step = self.step.min(last);
+
+        let (next, wrapped) = match self.direction(sequence) {
+            Direction::Forward => (Self::forward(step, last), step == last),
+            Direction::Reverse => (Self::backward(step, last), step == 0),
+            Direction::PingPong | Direction::PingPongRepeat if last == 0 => (0, true),
+            Direction::PingPong => {
+                if (self.backward && step == 0) || (!self.backward && step == last) {
+                    self.backward = !self.backward;
+                }
+                if self.backward {
+                    (step - 1, step == 1)
+                } else {
+                    (step + 1, false)
+                }
+            }
+            Direction::PingPongRepeat => {
+                if self.backward && step == 0 {
+                    self.backward = false;
+                    (0, true)
+                } else if !self.backward && step == last {
+                    self.backward = true;
+                    (last, false)
+                } else if self.backward {
+                    (step - 1, false)
+                } else {
+                    (step + 1, false)
+                }
+            }
+            Direction::Random => {
+                let next = (self.random.next_u32() % (last as u32 + 1)) as StepCounterType;
+                (next, self.count_loop_step(last))
+            }
+            Direction::Brownian => {
+                let next = match self.random.next_u32() % 4 {
+                    0 => Self::backward(step, last),
+                    1 => step,
+                    _ => Self::forward(step, last),
+                };
+                (next, self.count_loop_step(last))
+            }
+        };
+        self.step = next;
+
+        wrapped
+    }
+
+    /// Count a step of a random direction and return `true` if it completes the loop
+    fn count_loop_step(&mut self, last: StepCounterType) -> bool {
+        self.loop_steps += 1;
+        if self.loop_steps > last {
+            self.loop_steps = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn forward(step: StepCounterType, last: StepCounterType) -> StepCounterType {
+        if step < last {
+            step + 1
+        } else {
+            0
+        }
+    }
+
+    fn backward(step: StepCounterType, last: StepCounterType) -> StepCounterType {
+        if step > 0 {
+            step - 1
+        } else {
+            last
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::random::DEFAULT_SEED;
+    use crate::seq;
+
+    fn play(direction: Direction, count: usize) -> Vec<StepCounterType> {
+        play_seeded(direction, count, DEFAULT_SEED)
+    }
+
+    fn play_seeded(direction: Direction, count: usize, seed: u32) -> Vec<StepCounterType> {
+        let sequence = seq!(1, 2, 3, 4);
+        let mut playhead = Playhead::new(seed);
+        playhead.set_direction(Some(direction));
+        playhead.start(&sequence);
+        (0..count)
+            .map(|_| {
+                playhead.advance(&sequence);
+                playhead.step()
+            })
+            .collect()
+    }
+
+    /// Return the number of steps after which the playhead wrapped
+    fn wraps(direction: Direction, count: usize) -> Vec<usize> {
+        let sequence = seq!(1, 2, 3, 4);
+        let mut playhead = Playhead::new(DEFAULT_SEED);
+        playhead.set_direction(Some(direction));
+        playhead.start(&sequence);
+        (1..=count)
+            .filter(|_| playhead.advance(&sequence))
+            .collect()
+    }
+
+    #[test]
+    fn advances_in_direction() {
+        assert_eq!(play(Direction::Forward, 5), [1, 2, 3, 0, 1]);
+        assert_eq!(play(Direction::Reverse, 5), [2, 1, 0, 3, 2]);
+        assert_eq!(play(Direction::PingPong, 8), [1, 2, 3, 2, 1, 0, 1, 2]);
+        assert_eq!(
+            play(Direction::PingPongRepeat, 9),
+            [1, 2, 3, 3, 2, 1, 0, 0, 1]
+        );
+        assert!(play(Direction::Random, 20).iter().all(|step| *step < 4));
+        let walk = play(Direction::Brownian, 20);
+        assert!(walk
+            .windows(2)
+            .all(|pair| matches!((pair[1] + 4 - pair[0]) % 4, 0 | 1 | 3)));
+    }
+
+    #[test]
+    fn wraps_at_the_start_of_the_loop() {
+        assert_eq!(wraps(Direction::Forward, 12), [4, 8, 12]);
+        assert_eq!(wraps(Direction::Reverse, 12), [4, 8, 12]);
+        assert_eq!(wraps(Direction::PingPong, 12), [6, 12]);
+        assert_eq!(wraps(Direction::PingPongRepeat, 16), [8, 16]);
+        assert_eq!(wraps(Direction::Random, 12), [4, 8, 12]);
+        assert_eq!(wraps(Direction::Brownian, 12), [4, 8, 12]);
+    }
+
+    #[test]
+    fn random_directions_depend_on_the_seed() {
+        assert_ne!(
+            play_seeded(Direction::Random, 20, 1),
+            play_seeded(Direction::Random, 20, 2)
+        );
+        assert_ne!(
+            play_seeded(Direction::Brownian, 20, 1),
+            play_seeded(Direction::Brownian, 20, 2)
+        );
+    }
+}
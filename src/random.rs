@@ -0,0 +1,59 @@
+/// Seed used if no other seed is provided (e.g. in host tests)
+pub const DEFAULT_SEED: u32 = 0x2517_5e9d;
+
+/// Small deterministic pseudo random number generator (xorshift32)
+///
+/// The same seed always produces the same sequence of numbers
+#[derive(Copy, Clone)]
+pub struct Random {
+    state: u32,
+}
+
+impl Random {
+    /// Create a new generator
+    ///
+    /// A seed of `0` would only produce zeros and is replaced by `DEFAULT_SEED`
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            state: if seed == 0 { DEFAULT_SEED } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Return `true` with a probability of `percent` (0-100)
+    pub fn chance(&mut self, percent: u8) -> bool {
+        (self.next_u32() % 100) < percent as u32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_produces_same_numbers() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+        assert_ne!(Random::new(0).next_u32(), 0);
+    }
+
+    #[test]
+    fn chance_respects_bounds() {
+        let mut random = Random::new(1);
+        assert!((0..100).all(|_| !random.chance(0)));
+        assert!((0..100).all(|_| random.chance(100)));
+        let hits = (0..1000).filter(|_| random.chance(50)).count();
+        assert!(hits > 400 && hits < 600);
+    }
+}
@@ -1,4 +1,6 @@
 mod task;
+mod task_queue;
 
-pub use task::TaskId;
 pub use task::Task;
+pub use task::TaskId;
+pub use task_queue::TaskQueue;
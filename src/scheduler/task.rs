@@ -1,12 +1,20 @@
+use crate::time::Instant;
+
 pub trait TaskId {}
 
+#[derive(Debug, Copy, Clone)]
 pub struct Task<T: TaskId> {
     pub id: T,
-    pub timestamp: u32,
+    pub timestamp: Instant,
 }
 
 impl<T: TaskId> Task<T> {
-    pub fn new(id: T, timestamp: u32) -> Self {
+    pub fn new(id: T, timestamp: Instant) -> Self {
         Self { id, timestamp }
     }
+
+    /// Return if the task is due at `now`
+    pub fn is_due(&self, now: Instant) -> bool {
+        now.is_at_or_after(self.timestamp)
+    }
 }
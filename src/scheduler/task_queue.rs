@@ -0,0 +1,183 @@
+use super::{Task, TaskId};
+use crate::time::Instant;
+
+/// Fixed-capacity queue of tasks sorted by their deadline
+///
+/// Deadlines are compared wrap-around-safe (see `Instant::is_before`).
+pub struct TaskQueue<T: TaskId + Copy + PartialEq, const N: usize> {
+    tasks: [Option<Task<T>>; N],
+    len: usize,
+}
+
+impl<T: TaskId + Copy + PartialEq, const N: usize> TaskQueue<T, N> {
+    pub fn new() -> Self {
+        Self {
+            tasks: [None; N],
+            len: 0,
+        }
+    }
+
+    /// Add the task to the queue
+    ///
+    /// Tasks with the same deadline keep their insertion order. Returns the task back if the
+    /// queue is full.
+    pub fn push(&mut self, task: Task<T>) -> Result<(), Task<T>> {
+        if self.len == N {
+            return Err(task);
+        }
+
+        let position = self.tasks[..self.len]
+            .iter()
+            .position(|queued| match queued {
+                Some(queued) => task.timestamp.is_before(queued.timestamp),
+                None => false,
+            })
+            .unwrap_or(self.len);
+        self.tasks[position..=self.len].rotate_right(1);
+        self.tasks[position] = Some(task);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Return the task with the earliest deadline without removing it
+    pub fn peek(&self) -> Option<&Task<T>> {
+        self.tasks[0].as_ref()
+    }
+
+    /// Remove and return the earliest task if it is due at `now`
+    pub fn pop_due(&mut self, now: Instant) -> Option<Task<T>> {
+        match self.peek() {
+            Some(task) if task.is_due(now) => self.remove(0),
+            _ => None,
+        }
+    }
+
+    /// Remove all tasks with the given id
+    ///
+    /// Returns if a task was removed
+    pub fn cancel(&mut self, id: T) -> bool {
+        let mut removed = false;
+        let mut i = 0;
+        while i < self.len {
+            match self.tasks[i] {
+                Some(task) if task.id == id => {
+                    self.remove(i);
+                    removed = true;
+                }
+                _ => i += 1,
+            }
+        }
+
+        removed
+    }
+
+    /// Move all tasks with the given id to the new deadline (or add the task if none is queued)
+    pub fn reschedule(&mut self, id: T, timestamp: Instant) -> Result<(), Task<T>> {
+        self.cancel(id);
+        self.push(Task::new(id, timestamp))
+    }
+
+    /// Remove all tasks
+    pub fn clear(&mut self) {
+        self.tasks = [None; N];
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn remove(&mut self, index: usize) -> Option<Task<T>> {
+        let task = self.tasks[index].take();
+        self.tasks[index..self.len].rotate_left(1);
+        self.len -= 1;
+
+        task
+    }
+}
+
+impl<T: TaskId + Copy + PartialEq, const N: usize> Default for TaskQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum TestTask {
+        A,
+        B,
+        C,
+    }
+
+    impl TaskId for TestTask {}
+
+    fn at(micros: u32) -> Instant {
+        Instant::from_micros(micros)
+    }
+
+    fn drain<const N: usize>(queue: &mut TaskQueue<TestTask, N>, now: Instant) -> Vec<TestTask> {
+        let mut ids = vec![];
+        while let Some(task) = queue.pop_due(now) {
+            ids.push(task.id);
+        }
+        ids
+    }
+
+    #[test]
+    fn tasks_are_sorted_by_deadline() {
+        let mut queue: TaskQueue<TestTask, 4> = TaskQueue::new();
+        queue.push(Task::new(TestTask::A, at(30))).unwrap();
+        queue.push(Task::new(TestTask::B, at(10))).unwrap();
+        queue.push(Task::new(TestTask::C, at(20))).unwrap();
+        queue.push(Task::new(TestTask::A, at(10))).unwrap();
+        assert!(queue.push(Task::new(TestTask::C, at(0))).is_err());
+
+        assert_eq!(drain(&mut queue, at(9)), []);
+        assert_eq!(
+            drain(&mut queue, at(20)),
+            [TestTask::B, TestTask::A, TestTask::C]
+        );
+        assert_eq!(queue.len(), 1);
+        assert_eq!(drain(&mut queue, at(100)), [TestTask::A]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn deadlines_after_overflow_are_sorted_last() {
+        let mut queue: TaskQueue<TestTask, 4> = TaskQueue::new();
+        queue.push(Task::new(TestTask::A, at(5))).unwrap();
+        queue
+            .push(Task::new(TestTask::B, at(u32::MAX - 5)))
+            .unwrap();
+        queue.push(Task::new(TestTask::C, at(u32::MAX))).unwrap();
+
+        assert_eq!(drain(&mut queue, at(u32::MAX - 10)), []);
+        assert_eq!(drain(&mut queue, at(u32::MAX)), [TestTask::B, TestTask::C]);
+        assert_eq!(drain(&mut queue, at(4)), []);
+        assert_eq!(drain(&mut queue, at(5)), [TestTask::A]);
+    }
+
+    #[test]
+    fn cancel_and_reschedule_by_id() {
+        let mut queue: TaskQueue<TestTask, 4> = TaskQueue::new();
+        queue.push(Task::new(TestTask::A, at(10))).unwrap();
+        queue.push(Task::new(TestTask::B, at(20))).unwrap();
+        queue.push(Task::new(TestTask::A, at(30))).unwrap();
+
+        assert!(queue.cancel(TestTask::A));
+        assert!(!queue.cancel(TestTask::C));
+        queue.reschedule(TestTask::B, at(5)).unwrap();
+        queue.reschedule(TestTask::C, at(1)).unwrap();
+
+        assert_eq!(drain(&mut queue, at(100)), [TestTask::C, TestTask::B]);
+    }
+}
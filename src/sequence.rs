@@ -1,84 +1,158 @@
-use ufmt::{derive::uDebug, uDebug, uDisplay, uWrite, Formatter};
+use ufmt::{derive::uDebug, uDisplay, uWrite, Formatter};
 
 use crate::dac_byte::DacByte;
+use crate::gate_length::GateLength;
+use crate::playhead::Direction;
+use crate::trig_condition::TrigCondition;
+
+/// Maximum number of steps a sequence can hold
+pub const MAX_STEPS: usize = 32;
+
+/// Maximum number of pulses a step can fire within one clock period
+pub const MAX_RATCHETS: u8 = 4;
+
+/// Number of bits of a step (the resolution of the DAC)
+const STEP_BITS: usize = 4;
+/// Number of steps packed into one byte
+const STEPS_PER_BYTE: usize = 8 / STEP_BITS;
 
 #[derive(Copy, Clone, uDebug)]
 pub struct Sequence {
-    length: usize,
-    s1: Option<DacByte>,
-    s2: Option<DacByte>,
-    s3: Option<DacByte>,
-    s4: Option<DacByte>,
-    s5: Option<DacByte>,
-    s6: Option<DacByte>,
-    s7: Option<DacByte>,
-    s8: Option<DacByte>,
+    length: u8,
+    /// The 4 bit values of the steps, packed two per byte (the even steps in the low nibble)
+    steps: [u8; MAX_STEPS / STEPS_PER_BYTE],
+    gate_lengths: Option<&'static [GateLength]>,
+    conditions: Option<&'static [TrigCondition]>,
+    ratchets: Option<&'static [u8]>,
+    direction: Direction,
 }
 
 impl Sequence {
-    pub const fn new(
-        length: usize,
-        s1: Option<DacByte>,
-        s2: Option<DacByte>,
-        s3: Option<DacByte>,
-        s4: Option<DacByte>,
-        s5: Option<DacByte>,
-        s6: Option<DacByte>,
-        s7: Option<DacByte>,
-        s8: Option<DacByte>,
-    ) -> Self {
-        Self {
-            length,
-            s1,
-            s2,
-            s3,
-            s4,
-            s5,
-            s6,
-            s7,
-            s8,
+    /// Create a new sequence from the given steps
+    ///
+    /// Panics if more than `MAX_STEPS` or no steps are given
+    pub const fn new(steps: &[DacByte]) -> Self {
+        if steps.is_empty() {
+            panic!("Sequence must not be empty");
+        }
+        if steps.len() > MAX_STEPS {
+            panic!("Sequence step overflow");
+        }
+
+        let mut sequence = Self {
+            length: steps.len() as u8,
+            steps: [0; MAX_STEPS / STEPS_PER_BYTE],
+            gate_lengths: None,
+            conditions: None,
+            ratchets: None,
+            direction: Direction::Forward,
+        };
+        let mut i = 0;
+        while i < steps.len() {
+            sequence.steps[i / STEPS_PER_BYTE] |= steps[i].value() << Self::shift(i);
+            i += 1;
         }
+        sequence
     }
 
-    pub fn get_step(&self, step: u8) -> Option<DacByte> {
-        match step {
-            0b00000001 => self.s1,
-            0b00000010 => self.s2,
-            0b00000100 => self.s3,
-            0b00001000 => self.s4,
-            0b00010000 => self.s5,
-            0b00100000 => self.s6,
-            0b01000000 => self.s7,
-            0b10000000 => self.s8,
-            _ => None,
+    /// Return a copy of the sequence with individual gate lengths for the steps
+    ///
+    /// Steps without an entry in `gate_lengths` fall back to `GateLength::TriggerMode`
+    pub const fn with_gate_lengths(mut self, gate_lengths: &'static [GateLength]) -> Self {
+        self.gate_lengths = Some(gate_lengths);
+        self
+    }
+
+    /// Return a copy of the sequence with trig conditions for the steps
+    ///
+    /// Steps without an entry in `conditions` fall back to `TrigCondition::Always`
+    pub const fn with_conditions(mut self, conditions: &'static [TrigCondition]) -> Self {
+        self.conditions = Some(conditions);
+        self
+    }
+
+    /// Return a copy of the sequence with the number of pulses (1-`MAX_RATCHETS`) per step
+    ///
+    /// Steps without an entry in `ratchets` fire a single pulse
+    pub const fn with_ratchets(mut self, ratchets: &'static [u8]) -> Self {
+        self.ratchets = Some(ratchets);
+        self
+    }
+
+    /// Return a copy of the sequence that is played in the given direction
+    pub const fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Return the direction in which the sequence is played
+    pub fn get_direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Return the value of the step at `index` or `None` if it is outside of the sequence
+    pub fn get_step(&self, index: usize) -> Option<DacByte> {
+        if index < self.len() {
+            let value = self.steps[index / STEPS_PER_BYTE] >> Self::shift(index);
+            Some(DacByte::new(value & DacByte::max().value()))
+        } else {
+            None
         }
     }
 
-    pub fn matches(&self, step: u8) -> bool {
-        match step {
-            0b00000001 => self.s1.is_some() && self.s1.unwrap().value() > 0,
-            0b00000010 => self.s2.is_some() && self.s2.unwrap().value() > 0,
-            0b00000100 => self.s3.is_some() && self.s3.unwrap().value() > 0,
-            0b00001000 => self.s4.is_some() && self.s4.unwrap().value() > 0,
-            0b00010000 => self.s5.is_some() && self.s5.unwrap().value() > 0,
-            0b00100000 => self.s6.is_some() && self.s6.unwrap().value() > 0,
-            0b01000000 => self.s7.is_some() && self.s7.unwrap().value() > 0,
-            0b10000000 => self.s8.is_some() && self.s8.unwrap().value() > 0,
-            _ => {
-                use void::ResultVoidExt;
+    /// Change the value of the step at `index` (ignored if it is outside of the sequence)
+    pub fn set_step(&mut self, index: usize, value: DacByte) {
+        if index < self.len() {
+            let byte = &mut self.steps[index / STEPS_PER_BYTE];
+            *byte &= !(DacByte::max().value() << Self::shift(index));
+            *byte |= value.value() << Self::shift(index);
+        }
+    }
 
-                let mut serial: arduino_uno::Serial<arduino_uno::hal::port::mode::Floating> =
-                    unsafe { core::mem::MaybeUninit::uninit().assume_init() };
+    /// Return if the step at `index` should fire a trigger
+    pub fn matches(&self, index: usize) -> bool {
+        match self.get_step(index) {
+            Some(step) => step.value() > 0,
+            None => false,
+        }
+    }
 
-                ufmt::uwriteln!(&mut serial, "Step {} out of bounds!\r", step).void_unwrap();
+    pub fn get_gate_length(&self, index: usize) -> GateLength {
+        match self.gate_lengths {
+            Some(gate_lengths) if index < self.len() => gate_lengths
+                .get(index)
+                .copied()
+                .unwrap_or(GateLength::TriggerMode),
+            _ => GateLength::TriggerMode,
+        }
+    }
 
-                panic!()
-            }
+    pub fn get_condition(&self, index: usize) -> TrigCondition {
+        match self.conditions {
+            Some(conditions) if index < self.len() => conditions
+                .get(index)
+                .copied()
+                .unwrap_or(TrigCondition::Always),
+            _ => TrigCondition::Always,
+        }
+    }
+
+    pub fn get_ratchets(&self, index: usize) -> u8 {
+        match self.ratchets {
+            Some(ratchets) if index < self.len() => ratchets
+                .get(index)
+                .map_or(1, |count| (*count).clamp(1, MAX_RATCHETS)),
+            _ => 1,
         }
     }
 
     pub fn len(&self) -> usize {
-        self.length
+        self.length as usize
+    }
+
+    /// Return the position of the step at `index` within its byte
+    const fn shift(index: usize) -> usize {
+        (index % STEPS_PER_BYTE) * STEP_BITS
     }
 }
 
@@ -88,115 +162,23 @@ impl uDisplay for Sequence {
     where
         W: uWrite + ?Sized,
     {
-        <Sequence as uDebug>::fmt(self, f)
+        f.write_str("[")?;
+        for index in 0..self.len() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            if let Some(step) = self.get_step(index) {
+                <u8 as uDisplay>::fmt(&step.value(), f)?;
+            }
+        }
+        f.write_str("]")
     }
 }
 
 #[macro_export]
 macro_rules! seq {
-    ($b1:expr $(,)?) => {
-        Sequence::new(
-            1,
-            Some(DacByte::new($b1)),
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-        )
-    };
-    ($b1:expr, $b2:expr $(,)?) => {
-        Sequence::new(
-            2,
-            Some(DacByte::new($b1)),
-            Some(DacByte::new($b2)),
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-        )
-    };
-    ($b1:expr, $b2:expr, $b3:expr $(,)?) => {
-        Sequence::new(
-            3,
-            Some(DacByte::new($b1)),
-            Some(DacByte::new($b2)),
-            Some(DacByte::new($b3)),
-            None,
-            None,
-            None,
-            None,
-            None,
-        )
-    };
-    ($b1:expr, $b2:expr, $b3:expr, $b4:expr $(,)?) => {
-        Sequence::new(
-            4,
-            Some(DacByte::new($b1)),
-            Some(DacByte::new($b2)),
-            Some(DacByte::new($b3)),
-            Some(DacByte::new($b4)),
-            None,
-            None,
-            None,
-            None,
-        )
-    };
-    ($b1:expr, $b2:expr, $b3:expr, $b4:expr, $b5:expr $(,)?) => {
-        Sequence::new(
-            5,
-            Some(DacByte::new($b1)),
-            Some(DacByte::new($b2)),
-            Some(DacByte::new($b3)),
-            Some(DacByte::new($b4)),
-            Some(DacByte::new($b5)),
-            None,
-            None,
-            None,
-        )
-    };
-    ($b1:expr, $b2:expr, $b3:expr, $b4:expr, $b5:expr, $b6:expr $(,)?) => {
-        Sequence::new(
-            6,
-            Some(DacByte::new($b1)),
-            Some(DacByte::new($b2)),
-            Some(DacByte::new($b3)),
-            Some(DacByte::new($b4)),
-            Some(DacByte::new($b5)),
-            Some(DacByte::new($b6)),
-            None,
-            None,
-        )
-    };
-    ($b1:expr, $b2:expr, $b3:expr, $b4:expr, $b5:expr, $b6:expr, $b7:expr $(,)?) => {
-        Sequence::new(
-            7,
-            Some(DacByte::new($b1)),
-            Some(DacByte::new($b2)),
-            Some(DacByte::new($b3)),
-            Some(DacByte::new($b4)),
-            Some(DacByte::new($b5)),
-            Some(DacByte::new($b6)),
-            Some(DacByte::new($b7)),
-            None,
-        )
-    };
-    ($b1:expr, $b2:expr, $b3:expr, $b4:expr, $b5:expr, $b6:expr, $b7:expr, $b8:expr $(,)?) => {
-        Sequence::new(
-            8,
-            Some(DacByte::new($b1)),
-            Some(DacByte::new($b2)),
-            Some(DacByte::new($b3)),
-            Some(DacByte::new($b4)),
-            Some(DacByte::new($b5)),
-            Some(DacByte::new($b6)),
-            Some(DacByte::new($b7)),
-            Some(DacByte::new($b8)),
-        )
+    ($($b:expr),+ $(,)?) => {
+        $crate::sequence::Sequence::new(&[$($crate::dac_byte::DacByte::new($b)),+])
     };
 }
 
@@ -204,7 +186,7 @@ mod test {
     use super::*;
 
     #[allow(unused)]
-    const SEQUENCES: [Sequence; 8] = [
+    const SEQUENCES: [Sequence; 10] = [
         seq!(1),
         seq!(15, 13),
         seq!(15, 13, 1),
@@ -213,5 +195,30 @@ mod test {
         seq!(15, 0, 1, 0, 15, 2),
         seq!(15, 0, 1, 0, 15, 2, 7),
         seq!(15, 0, 1, 0, 15, 2, 7, 15),
+        seq!(15, 0, 1, 0, 15, 2, 7, 15, 0, 3, 3, 3),
+        seq!(
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10,
+            11, 12, 13, 14, 15, 0
+        ),
     ];
+
+    #[test]
+    fn steps_outside_of_the_sequence_do_not_match() {
+        let sequence = SEQUENCES[8];
+        assert_eq!(sequence.len(), 12);
+        assert_eq!(sequence.get_step(11).map(|s| s.value()), Some(3));
+        assert!(sequence.matches(11));
+        assert!(!sequence.matches(1));
+        assert!(sequence.get_step(12).is_none());
+        assert!(!sequence.matches(12));
+        assert!(!sequence.matches(MAX_STEPS));
+
+        let mut sequence = SEQUENCES[9];
+        assert_eq!(sequence.len(), MAX_STEPS);
+        assert_eq!(sequence.get_step(30).map(|s| s.value()), Some(15));
+        sequence.set_step(30, DacByte::new(6));
+        assert_eq!(sequence.get_step(29).map(|s| s.value()), Some(14));
+        assert_eq!(sequence.get_step(30).map(|s| s.value()), Some(6));
+        assert_eq!(sequence.get_step(31).map(|s| s.value()), Some(0));
+    }
 }
@@ -1,62 +1,367 @@
+use crate::euclid::Euclid;
 use crate::sequence::Sequence;
+use crate::tap_tempo::{Tap, TapTempo, TAP_WINDOW};
+use crate::time::{Duration, Instant};
 use crate::SEQUENCES;
-use arduino_uno::hal::port::mode::{Input, PullUp};
-use arduino_uno::hal::port::portc::PC5;
-use arduino_uno::prelude::*;
+use core::str::FromStr;
+use embedded_hal::digital::v2::InputPin;
+use void::{ResultVoidExt, Void};
 
-type SequenceChangeInput = PC5<Input<PullUp>>;
+/// Minimum time the button has to be released before a press counts (longer than its bounces)
+pub const DEBOUNCE_TIME: Duration = Duration::from_millis(20);
+/// Minimum time the button has to be held to start tapping the tempo
+pub const LONG_PRESS: Duration = Duration::from_millis(1000);
 
-pub struct SequenceController {
+/// When a sequence change from the button takes effect
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SwitchMode {
+    /// Switch right away and restart the clock
+    Immediate,
+    /// Start the new sequence with the next clock-trigger
+    NextStep,
+    /// Start the new sequence when the playhead wraps around
+    EndOfLoop,
+}
+
+impl FromStr for SwitchMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "immediate" => Ok(SwitchMode::Immediate),
+            "next-step" => Ok(SwitchMode::NextStep),
+            "end-of-loop" => Ok(SwitchMode::EndOfLoop),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Entry of a song: the sequence at index `sequence` is played `repeats` times
+///
+/// `repeats` must be at least 1, a song with an entry that is never played is rejected.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SongEntry {
+    pub sequence: usize,
+    pub repeats: u8,
+}
+
+impl SongEntry {
+    pub const fn new(sequence: usize, repeats: u8) -> Self {
+        Self { sequence, repeats }
+    }
+}
+
+/// Return if `song` can be played from `sequence_count` sequences: it has entries, and each of them
+/// plays an existing sequence at least once
+pub const fn is_valid_song(song: &[SongEntry], sequence_count: usize) -> bool {
+    if song.is_empty() {
+        return false;
+    }
+    let mut i = 0;
+    while i < song.len() {
+        if song[i].sequence >= sequence_count || song[i].repeats == 0 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+impl FromStr for SongEntry {
+    type Err = ();
+
+    /// Parse `SEQUENCE:REPEATS` (`REPEATS` must be at least 1)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let sequence = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+        let repeats = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+        if repeats == 0 {
+            return Err(());
+        }
+
+        Ok(Self::new(sequence, repeats))
+    }
+}
+
+pub struct SequenceController<IN: InputPin<Error = Void>> {
     sequences: &'static [Sequence],
-    sequence_change_input: SequenceChangeInput,
+    /// Generator that replaces `sequences` (the pointer is the number of pulses) and the sequence
+    /// it generated for the current pulse count
+    euclid: Option<(Euclid, Sequence)>,
+    /// Order in which the sequences are played (the pointer is the position in the song)
+    song: Option<&'static [SongEntry]>,
+    /// Number of finished loops of the current song entry
+    repeats: u8,
+    switch_mode: SwitchMode,
+    /// Sequence that will be played once the `switch_mode` allows it
+    pending_sequence_pointer: Option<usize>,
+    sequence_change_input: IN,
     sequence_pointer: usize,
     last_sequence_change_state: bool,
+    /// Time the button was last released
+    last_release: Option<Instant>,
+    /// Time the button was pressed while it is held and the press is not yet a long press
+    press_start: Option<Instant>,
+    /// Time of the long press or the last tap while the presses are taps
+    last_tap: Option<Instant>,
+    tap_tempo: TapTempo,
 }
 
 pub struct SequenceState {
-    pub sequence: Sequence,
     pub sequence_pointer: usize,
     pub did_change: bool,
+    /// `true` if a press changed the pending sequence
+    pub did_queue: bool,
+    /// Step interval if the button is tapped rhythmically
+    pub tap_interval: Option<Duration>,
 }
 
-impl SequenceController {
-    pub fn new(sequence_change_input: SequenceChangeInput) -> Self {
+impl<IN: InputPin<Error = Void>> SequenceController<IN> {
+    pub fn new(sequence_change_input: IN) -> Self {
+        Self::with_sequences(sequence_change_input, &SEQUENCES)
+    }
+
+    /// Create a controller that cycles through the given sequences instead of `SEQUENCES`
+    pub fn with_sequences(sequence_change_input: IN, sequences: &'static [Sequence]) -> Self {
         Self {
-            sequences: &SEQUENCES,
+            sequences,
+            euclid: None,
+            song: None,
+            repeats: 0,
+            switch_mode: SwitchMode::Immediate,
+            pending_sequence_pointer: None,
             sequence_change_input,
             sequence_pointer: 0,
             last_sequence_change_state: false,
+            last_release: None,
+            press_start: None,
+            last_tap: None,
+            tap_tempo: TapTempo::new(),
         }
     }
 
-    pub fn check_sequence_change(&mut self) -> SequenceState {
-        let last_sequence_change_trigger_state = self.last_sequence_change_state;
-        let sequences = self.sequences;
-        let mut new_sequence_pointer = self.sequence_pointer;
+    /// Create a controller that plays Euclidean rhythms and cycles through their pulse counts
+    pub fn with_euclid(sequence_change_input: IN, euclid: Euclid) -> Self {
+        let mut controller = Self::new(sequence_change_input);
+        controller.sequence_pointer = euclid.pulses() as usize;
+        controller.euclid = Some((euclid, euclid.sequence()));
+        controller
+    }
+
+    /// Create a controller that plays the entries of `song` one after the other
+    ///
+    /// A button press skips to the next entry of the song. Returns `None` if the song is not
+    /// valid (see `is_valid_song()`).
+    pub fn with_song(
+        sequence_change_input: IN,
+        sequences: &'static [Sequence],
+        song: &'static [SongEntry],
+    ) -> Option<Self> {
+        if !is_valid_song(song, sequences.len()) {
+            return None;
+        }
+        let mut controller = Self::with_sequences(sequence_change_input, sequences);
+        controller.song = Some(song);
+        Some(controller)
+    }
 
+    /// Change when a sequence change from the button takes effect
+    pub fn set_switch_mode(&mut self, switch_mode: SwitchMode) {
+        self.switch_mode = switch_mode
+    }
+
+    /// Return the sequence that will be played once the `switch_mode` allows it
+    pub fn pending_sequence(&self) -> Option<Sequence> {
+        self.pending_sequence_pointer
+            .map(|pointer| self.sequence_at(pointer))
+    }
+
+    /// Check the button for a press (next sequence) or taps (tap tempo)
+    ///
+    /// Presses within `DEBOUNCE_TIME` after a release are bounces and ignored.
+    ///
+    /// A short press switches to the next sequence immediately or queues it, depending on the
+    /// `switch_mode`. It takes effect when the button is released, as only then it is clear that
+    /// the press is not a long press. Holding the button for `LONG_PRESS` starts tapping: the
+    /// following presses set the tempo and leave the sequence alone, until there is no tap for
+    /// `TAP_WINDOW`.
+    pub fn check_sequence_change(&mut self, now: Instant) -> SequenceState {
+        let last_sequence_change_trigger_state = self.last_sequence_change_state;
         let sequence_change_input: bool = self.sequence_change_input.is_low().void_unwrap();
-        let did_change = sequence_change_input && false == last_sequence_change_trigger_state;
+        self.last_sequence_change_state = sequence_change_input;
+
+        let released = !sequence_change_input && last_sequence_change_trigger_state;
+        if released {
+            self.last_release = Some(now);
+        }
+        let bouncing = matches!(self.last_release,
+            Some(release) if now.duration_since(release) < DEBOUNCE_TIME);
+        let pressed =
+            sequence_change_input && false == last_sequence_change_trigger_state && !bouncing;
+
+        let mut state = SequenceState {
+            sequence_pointer: self.sequence_pointer,
+            did_change: false,
+            did_queue: false,
+            tap_interval: None,
+        };
 
-        if did_change {
-            new_sequence_pointer += 1;
-            if sequences.len() <= new_sequence_pointer {
-                new_sequence_pointer = 0;
+        if matches!(self.last_tap, Some(last_tap) if now.duration_since(last_tap) > TAP_WINDOW) {
+            self.last_tap = None;
+        }
+        if pressed {
+            if self.last_tap.is_some() {
+                self.last_tap = Some(now);
+                if let Tap::Second(interval) | Tap::Next(interval) = self.tap_tempo.tap(now) {
+                    state.tap_interval = Some(interval);
+                }
+            } else {
+                self.press_start = Some(now);
+            }
+        }
+        match self.press_start {
+            Some(press_start) if released => {
+                self.press_start = None;
+                if now.duration_since(press_start) < LONG_PRESS {
+                    self.next_sequence(&mut state);
+                }
+            }
+            Some(press_start) if now.duration_since(press_start) >= LONG_PRESS => {
+                self.press_start = None;
+                self.last_tap = Some(now);
+                self.tap_tempo = TapTempo::new();
             }
+            _ => {}
+        }
+
+        state
+    }
 
-            self.sequence_pointer = new_sequence_pointer;
+    /// Switch to the next sequence or queue it, depending on the `switch_mode`
+    fn next_sequence(&mut self, state: &mut SequenceState) {
+        let mut pointer = self
+            .pending_sequence_pointer
+            .unwrap_or(self.sequence_pointer)
+            + 1;
+        if self.sequence_count() <= pointer {
+            pointer = 0;
         }
 
-        self.last_sequence_change_state = sequence_change_input;
+        if self.switch_mode == SwitchMode::Immediate {
+            self.switch_to(pointer);
+            state.sequence_pointer = pointer;
+            state.did_change = true;
+        } else {
+            self.pending_sequence_pointer =
+                Some(pointer).filter(|pointer| *pointer != self.sequence_pointer);
+            state.did_queue = true;
+        }
+    }
 
-        SequenceState {
-            sequence: self.sequences[self.sequence_pointer],
-            sequence_pointer: self.sequence_pointer,
-            did_change,
+    /// Select the sequence at `pointer` right away, regardless of the `switch_mode`
+    ///
+    /// Returns `false` (and keeps the current sequence) if there is no sequence at `pointer`.
+    pub fn select_sequence(&mut self, pointer: usize) -> bool {
+        if pointer >= self.sequence_count() {
+            return false;
+        }
+        self.switch_to(pointer);
+        true
+    }
+
+    /// Return the currently selected sequence
+    pub fn get_sequence(&self) -> &Sequence {
+        match &self.euclid {
+            Some((_, sequence)) => sequence,
+            None => self.table_sequence(self.sequence_pointer),
         }
     }
 
-    #[allow(unused)]
-    pub fn get_sequence(&self) -> Sequence {
-        self.sequences[self.sequence_pointer]
+    /// Switch to the pending sequence (if the `switch_mode` allows it) or advance the song after
+    /// a step was played
+    ///
+    /// `loop_end` tells if the playhead wrapped around. Returns `true` if the sequence changed.
+    pub fn finish_step(&mut self, loop_end: bool) -> bool {
+        if self.switch_mode == SwitchMode::NextStep || loop_end {
+            if let Some(pointer) = self.pending_sequence_pointer.take() {
+                self.switch_to(pointer);
+                return true;
+            }
+        }
+        if !loop_end {
+            return false;
+        }
+
+        let song = match self.song {
+            Some(song) => song,
+            None => return false,
+        };
+        self.repeats = self.repeats.saturating_add(1);
+        if self.repeats < song[self.sequence_pointer].repeats {
+            return false;
+        }
+        let pointer = (self.sequence_pointer + 1) % song.len();
+        let did_change = song[pointer].sequence != song[self.sequence_pointer].sequence;
+        self.switch_to(pointer);
+
+        did_change
+    }
+
+    fn switch_to(&mut self, pointer: usize) {
+        self.sequence_pointer = pointer;
+        self.pending_sequence_pointer = None;
+        self.repeats = 0;
+        if let Some((euclid, sequence)) = self.euclid.as_mut() {
+            euclid.set_pulses(pointer as u8);
+            *sequence = euclid.sequence();
+        }
+    }
+
+    fn sequence_count(&self) -> usize {
+        match (self.euclid, self.song) {
+            (Some((euclid, _)), _) => euclid.steps() as usize + 1,
+            (None, Some(song)) => song.len(),
+            (None, None) => self.sequences.len(),
+        }
+    }
+
+    fn sequence_at(&self, pointer: usize) -> Sequence {
+        match self.euclid {
+            Some((mut euclid, _)) => {
+                euclid.set_pulses(pointer as u8);
+                euclid.sequence()
+            }
+            None => *self.table_sequence(pointer),
+        }
+    }
+
+    fn table_sequence(&self, pointer: usize) -> &'static Sequence {
+        match self.song {
+            Some(song) => &self.sequences[song[pointer].sequence],
+            None => &self.sequences[pointer],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::MockInputPin;
+
+    #[test]
+    fn rejects_songs_that_cannot_be_played() {
+        static EMPTY: [SongEntry; 0] = [];
+        static MISSING_SEQUENCE: [SongEntry; 2] = [SongEntry::new(0, 1), SongEntry::new(12, 1)];
+        static NO_REPEATS: [SongEntry; 1] = [SongEntry::new(0, 0)];
+        static SONG: [SongEntry; 2] = [SongEntry::new(0, 1), SongEntry::new(11, 2)];
+        let with_song =
+            |song| SequenceController::with_song(MockInputPin::new(), &SEQUENCES, song).is_some();
+
+        assert!(!with_song(&EMPTY));
+        assert!(!with_song(&MISSING_SEQUENCE));
+        assert!(!with_song(&NO_REPEATS));
+        assert!(with_song(&SONG));
+        assert_eq!("3:0".parse::<SongEntry>(), Err(()));
+        assert_eq!("3:2".parse::<SongEntry>(), Ok(SongEntry::new(3, 2)));
     }
 }
@@ -1,24 +1,23 @@
-use arduino_uno::hal::port::mode::InputMode;
-use arduino_uno::Serial;
 use ufmt::uWrite;
+use void::Void;
 
-pub struct SerialWrapper<IMODE: InputMode> {
+pub struct SerialWrapper<S: uWrite<Error = Void>> {
     debug: bool,
-    serial: Serial<IMODE>,
+    serial: S,
 }
 
-impl<IMODE: InputMode> SerialWrapper<IMODE> {
-    pub fn new(debug: bool, serial: Serial<IMODE>) -> Self {
+impl<S: uWrite<Error = Void>> SerialWrapper<S> {
+    pub fn new(debug: bool, serial: S) -> Self {
         SerialWrapper { debug, serial }
     }
 
-    pub fn get_serial(&mut self) -> &mut Serial<IMODE> {
+    pub fn get_serial(&mut self) -> &mut S {
         &mut self.serial
     }
 }
 
-impl<IMODE: InputMode> uWrite for SerialWrapper<IMODE> {
-    type Error = void::Void;
+impl<S: uWrite<Error = Void>> uWrite for SerialWrapper<S> {
+    type Error = Void;
 
     fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
         if cfg!(feature = "debug") && self.debug {
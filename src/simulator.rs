@@ -0,0 +1,218 @@
+//! Run the sequencer on the host against the mocked peripherals
+use crate::clock::ClockFactory;
+use crate::euclid::Euclid;
+use crate::mock::{MockApp, MockClock, MockPeripherals};
+use crate::playhead::Direction;
+use crate::random::DEFAULT_SEED;
+use crate::sequence::Sequence;
+use crate::sequence_controller::{SequenceController, SongEntry, SwitchMode};
+use crate::trigger::TriggerFactory;
+use crate::turing_machine::TuringMachine;
+use crate::{RGB_LED_COUNT, SEQUENCES, STEP_LED_COUNT};
+use embedded_hal::digital::v2::InputPin;
+use smart_leds::RGB8;
+use void::ResultVoidExt;
+
+/// Square wave that is fed into the clock input
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClockSignal {
+    /// Interval between two rising edges in milliseconds
+    pub interval: u32,
+    /// Duration in milliseconds how long the signal stays high
+    pub pulse_width: u32,
+}
+
+impl ClockSignal {
+    pub fn is_high(&self, millis: u32) -> bool {
+        self.interval > 0 && millis % self.interval < self.pulse_width
+    }
+}
+
+/// State of all simulated pins at one point in time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub millis: u32,
+    pub clock_input: bool,
+    pub trigger_output: bool,
+    pub step_outputs: [bool; STEP_LED_COUNT],
+    pub sequence_change_output: bool,
+    /// Value of the four DAC bits
+    pub dac: u8,
+    pub leds: [RGB8; RGB_LED_COUNT],
+}
+
+pub struct Simulator {
+    peripherals: MockPeripherals,
+    app: MockApp,
+    clock_signal: Option<ClockSignal>,
+    run_counter: u32,
+    millis: u32,
+}
+
+impl Simulator {
+    pub fn new(clock_factory: ClockFactory<MockClock>, trigger_factory: TriggerFactory) -> Self {
+        Self::with_sequences(clock_factory, trigger_factory, &SEQUENCES)
+    }
+
+    /// Create a simulator that plays the given sequences instead of `SEQUENCES`
+    pub fn with_sequences(
+        clock_factory: ClockFactory<MockClock>,
+        trigger_factory: TriggerFactory,
+        sequences: &'static [Sequence],
+    ) -> Self {
+        let peripherals = MockPeripherals::new();
+        let app = peripherals.build_app_with_sequences(clock_factory, trigger_factory, sequences);
+
+        Self::with_app(peripherals, app)
+    }
+
+    /// Create a simulator that plays the Euclidean rhythms of `euclid` instead of `SEQUENCES`
+    pub fn with_euclid(
+        clock_factory: ClockFactory<MockClock>,
+        trigger_factory: TriggerFactory,
+        euclid: Euclid,
+    ) -> Self {
+        let peripherals = MockPeripherals::new();
+        let app = peripherals.build_app_with_euclid(clock_factory, trigger_factory, euclid);
+
+        Self::with_app(peripherals, app)
+    }
+
+    /// Create a simulator that plays the entries of `song`
+    ///
+    /// Returns `None` if the song is not valid (see `is_valid_song()`).
+    pub fn with_song(
+        clock_factory: ClockFactory<MockClock>,
+        trigger_factory: TriggerFactory,
+        song: &'static [SongEntry],
+    ) -> Option<Self> {
+        let peripherals = MockPeripherals::new();
+        let sequence_controller = SequenceController::with_song(
+            peripherals.sequence_change_input.clone(),
+            &SEQUENCES,
+            song,
+        )?;
+        let app = peripherals.build_app_with_sequence_controller(
+            clock_factory,
+            trigger_factory,
+            sequence_controller,
+        );
+
+        Some(Self::with_app(peripherals, app))
+    }
+
+    fn with_app(peripherals: MockPeripherals, mut app: MockApp) -> Self {
+        app.initialize_leds();
+
+        Self {
+            peripherals,
+            app,
+            clock_signal: None,
+            run_counter: 0,
+            millis: 0,
+        }
+    }
+
+    /// Feed the given signal into the clock input (`None` keeps the input low)
+    pub fn set_clock_signal(&mut self, clock_signal: Option<ClockSignal>) {
+        self.clock_signal = clock_signal
+    }
+
+    /// Set the level of the reset input
+    pub fn set_reset_input(&mut self, high: bool) {
+        self.peripherals.reset_input.set(high)
+    }
+
+    /// Set the level of the run/stop gate input
+    pub fn set_run_input(&mut self, high: bool) {
+        self.peripherals.run_input.set(high)
+    }
+
+    /// Override the direction of the sequences (`None` uses the direction of each sequence)
+    pub fn set_direction(&mut self, direction: Option<Direction>) {
+        self.app.set_direction(direction)
+    }
+
+    /// Delay every second step by `swing` percent of the clock interval
+    pub fn set_swing(&mut self, swing: u8) {
+        self.app.set_swing(swing)
+    }
+
+    /// Mutate the sequences with a chance of `probability` percent per step (`None` turns the
+    /// Turing machine mode off)
+    pub fn set_turing_machine(&mut self, probability: Option<u8>) {
+        let turing_machine = probability.map(|probability| {
+            let mut turing_machine = TuringMachine::new(DEFAULT_SEED);
+            turing_machine.set_probability(probability);
+            turing_machine
+        });
+        self.app.set_turing_machine(turing_machine)
+    }
+
+    /// Change when a sequence change from the button takes effect
+    pub fn set_switch_mode(&mut self, switch_mode: SwitchMode) {
+        self.app.set_switch_mode(switch_mode)
+    }
+
+    pub fn peripherals(&self) -> &MockPeripherals {
+        &self.peripherals
+    }
+
+    /// Play the sequence at `pointer` from the start (the number of pulses with `with_euclid()`,
+    /// the position in the song with `with_song()`)
+    ///
+    /// Returns `false` if there is no sequence at `pointer`.
+    pub fn select_sequence(&mut self, pointer: usize) -> bool {
+        self.app.select_sequence(pointer)
+    }
+
+    /// Press and release the sequence-change button
+    pub fn press_sequence_change_button(&mut self) {
+        self.peripherals.sequence_change_input.set(false);
+        self.run_loop();
+        self.peripherals.sequence_change_input.set(true);
+        self.run_loop();
+    }
+
+    /// Move the time forward to `millis` and run one iteration of the `App`'s loop
+    pub fn tick(&mut self, millis: u32) -> Snapshot {
+        self.millis = millis;
+        self.peripherals.time_source.set(millis);
+        if let Some(clock_signal) = self.clock_signal {
+            self.peripherals
+                .clock_input
+                .set(clock_signal.is_high(millis));
+        }
+        self.run_loop();
+
+        self.snapshot()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let peripherals = &self.peripherals;
+        let mut step_outputs = [false; STEP_LED_COUNT];
+        for (output, pin) in step_outputs
+            .iter_mut()
+            .zip(peripherals.step_output_pins.iter())
+        {
+            *output = pin.is_high();
+        }
+
+        Snapshot {
+            millis: self.millis,
+            clock_input: peripherals.clock_input.is_high().void_unwrap(),
+            trigger_output: peripherals.trigger_output.is_high(),
+            step_outputs,
+            sequence_change_output: peripherals.sequence_change_output.is_high(),
+            dac: peripherals.dac_value(),
+            leds: peripherals.leds.data(),
+        }
+    }
+
+    fn run_loop(&mut self) {
+        self.run_counter += 1;
+        self.app.run_loop(self.run_counter);
+        // Nobody reads the debug output in the simulation
+        self.peripherals.serial.take_output();
+    }
+}
@@ -0,0 +1,107 @@
+use crate::tempo_pot::STEPS_PER_BEAT;
+use crate::time::{Duration, Instant};
+
+/// Number of intervals between taps that are averaged
+pub const TAP_COUNT: usize = 4;
+/// Maximum interval between two taps of a series (40 BPM)
+pub const TAP_WINDOW: Duration = Duration::from_millis(1500);
+/// Minimum interval between two taps of a series (240 BPM); faster presses are single presses
+pub const MIN_TAP_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Meaning of a button press
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tap {
+    /// The press does not follow a recent press
+    Single,
+    /// The second press of a series (the first one that measures an interval)
+    Second(Duration),
+    /// Any further press of a series
+    Next(Duration),
+}
+
+/// Detects taps on a button and averages the intervals between them
+///
+/// The intervals are beats, the returned durations are the resulting step intervals.
+pub struct TapTempo {
+    last_tap: Option<Instant>,
+    intervals: [Duration; TAP_COUNT],
+    len: usize,
+    next: usize,
+}
+
+impl TapTempo {
+    pub const fn new() -> Self {
+        Self {
+            last_tap: None,
+            intervals: [Duration::from_micros(0); TAP_COUNT],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Register a press at `now`
+    pub fn tap(&mut self, now: Instant) -> Tap {
+        let interval = self.last_tap.map(|last_tap| now.duration_since(last_tap));
+        self.last_tap = Some(now);
+
+        match interval {
+            Some(interval) if interval >= MIN_TAP_INTERVAL && interval <= TAP_WINDOW => {
+                self.intervals[self.next] = interval;
+                self.next = (self.next + 1) % TAP_COUNT;
+                self.len = (self.len + 1).min(TAP_COUNT);
+
+                if self.len == 1 {
+                    Tap::Second(self.step_interval())
+                } else {
+                    Tap::Next(self.step_interval())
+                }
+            }
+            _ => {
+                self.len = 0;
+                self.next = 0;
+                Tap::Single
+            }
+        }
+    }
+
+    /// Return the average tapped beat divided into steps
+    fn step_interval(&self) -> Duration {
+        let sum = self.intervals[..self.len]
+            .iter()
+            .fold(Duration::from_micros(0), |sum, interval| sum + *interval);
+        sum / (self.len as u32 * STEPS_PER_BEAT)
+    }
+}
+
+impl Default for TapTempo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn averages_taps_within_window() {
+        let mut tap_tempo = TapTempo::new();
+        assert_eq!(tap_tempo.tap(Instant::from_millis(0)), Tap::Single);
+        assert_eq!(
+            tap_tempo.tap(Instant::from_millis(1000)),
+            Tap::Second(Duration::from_millis(250))
+        );
+        assert_eq!(
+            tap_tempo.tap(Instant::from_millis(1800)),
+            Tap::Next(Duration::from_millis(225))
+        );
+        // A pause ends the series
+        assert_eq!(tap_tempo.tap(Instant::from_millis(4000)), Tap::Single);
+        // Rapid presses are no taps
+        assert_eq!(tap_tempo.tap(Instant::from_millis(4100)), Tap::Single);
+        assert_eq!(
+            tap_tempo.tap(Instant::from_millis(4600)),
+            Tap::Second(Duration::from_millis(125))
+        );
+    }
+}
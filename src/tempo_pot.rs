@@ -0,0 +1,127 @@
+use crate::time::Duration;
+
+/// Highest value of the 10 bit ADC
+pub const ADC_MAX: u16 = 1023;
+/// Tempo at the lowest position of the pot
+pub const MIN_BPM: u16 = 40;
+/// Tempo at the highest position of the pot
+pub const MAX_BPM: u16 = 240;
+/// Number of steps per beat (sixteenth notes)
+pub const STEPS_PER_BEAT: u32 = 4;
+
+/// Weight of a new reading in the moving average (1/8)
+const SMOOTHING_SHIFT: u32 = 3;
+/// Change of the smoothed reading needed before the tempo follows the pot
+const HYSTERESIS: u16 = 6;
+
+/// Return the interval between the steps for the given tempo
+pub fn interval_for_bpm(bpm: u16) -> Duration {
+    Duration::from_micros(60_000_000 / (bpm.max(1) as u32 * STEPS_PER_BEAT))
+}
+
+/// Smooths the readings of a potentiometer on the ADC
+///
+/// The readings are smoothed with an exponential moving average, and the position only changes
+/// if the smoothed reading moved by more than `HYSTERESIS` (or reached an end of the pot), so the
+/// noise of the ADC does not make the value controlled by the pot jitter.
+pub struct PotFilter {
+    /// Moving average of the readings (scaled by `1 << SMOOTHING_SHIFT`)
+    smoothed: Option<u32>,
+    /// Smoothed reading of the current position
+    position: Option<u16>,
+}
+
+impl PotFilter {
+    pub const fn new() -> Self {
+        Self {
+            smoothed: None,
+            position: None,
+        }
+    }
+
+    /// Feed a reading from the ADC and return the new position (0-`ADC_MAX`) if it changed
+    pub fn update(&mut self, reading: u16) -> Option<u16> {
+        let reading = reading.min(ADC_MAX) as u32;
+        let smoothed = match self.smoothed {
+            Some(smoothed) => smoothed - (smoothed >> SMOOTHING_SHIFT) + reading,
+            None => reading << SMOOTHING_SHIFT,
+        };
+        self.smoothed = Some(smoothed);
+
+        let value = (smoothed >> SMOOTHING_SHIFT) as u16;
+        if let Some(position) = self.position {
+            let at_end = value == 0 || value == ADC_MAX;
+            if value == position
+                || (!at_end && value.max(position) - value.min(position) < HYSTERESIS)
+            {
+                return None;
+            }
+        }
+        self.position = Some(value);
+
+        Some(value)
+    }
+}
+
+impl Default for PotFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps the readings of a potentiometer on the ADC to a tempo
+pub struct TempoPot {
+    filter: PotFilter,
+    bpm: Option<u16>,
+}
+
+impl TempoPot {
+    pub const fn new() -> Self {
+        Self {
+            filter: PotFilter::new(),
+            bpm: None,
+        }
+    }
+
+    /// Feed a reading from the ADC and return the new tempo if it changed
+    pub fn update(&mut self, reading: u16) -> Option<u16> {
+        let value = self.filter.update(reading)?;
+        let bpm = MIN_BPM + (value as u32 * (MAX_BPM - MIN_BPM) as u32 / ADC_MAX as u32) as u16;
+        if self.bpm == Some(bpm) {
+            return None;
+        }
+        self.bpm = Some(bpm);
+
+        Some(bpm)
+    }
+
+    /// Return the current tempo
+    pub fn bpm(&self) -> Option<u16> {
+        self.bpm
+    }
+}
+
+impl Default for TempoPot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ignores_noise_and_follows_movement() {
+        let mut pot = TempoPot::new();
+        assert_eq!(pot.update(0), Some(MIN_BPM));
+        // Noise in the lower bits
+        for reading in [3, 0, 4, 1, 5, 2].iter() {
+            assert_eq!(pot.update(*reading), None);
+        }
+
+        let bpm = (0..100).filter_map(|_| pot.update(ADC_MAX)).last();
+        assert_eq!(bpm, Some(MAX_BPM));
+        assert_eq!(interval_for_bpm(60), Duration::from_millis(250));
+    }
+}
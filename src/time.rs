@@ -0,0 +1,175 @@
+//! Points in time and durations based on the wrapping microsecond counter
+//!
+//! The microsecond counter is a `u32` that overflows after ~71 minutes. All arithmetic wraps, so
+//! comparisons stay correct across the overflow as long as the compared instants are less than
+//! `i32::MAX` microseconds (~35 minutes) apart.
+use core::ops::{Add, AddAssign, Div, Mul, Sub};
+use ufmt::{uDebug, uDisplay, uWrite, Formatter};
+
+/// Point in time as read from the microsecond counter
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Instant(u32);
+
+/// Span of time in microseconds
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Duration(u32);
+
+impl Instant {
+    pub const fn from_micros(micros: u32) -> Self {
+        Self(micros)
+    }
+
+    /// Create an instant from a millisecond value (wrapping like the microsecond counter)
+    pub const fn from_millis(millis: u32) -> Self {
+        Self(millis.wrapping_mul(1000))
+    }
+
+    /// Return the raw value of the microsecond counter
+    pub const fn as_micros(self) -> u32 {
+        self.0
+    }
+
+    /// Return the time elapsed from `earlier` to `self`
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        Duration(self.0.wrapping_sub(earlier.0))
+    }
+
+    /// Return if `self` is before `other`, even if the counter overflowed in between
+    pub fn is_before(self, other: Instant) -> bool {
+        (self.0.wrapping_sub(other.0) as i32) < 0
+    }
+
+    /// Return if `self` is the same as or after `other`
+    pub fn is_at_or_after(self, other: Instant) -> bool {
+        !self.is_before(other)
+    }
+}
+
+impl Duration {
+    pub const fn from_micros(micros: u32) -> Self {
+        Self(micros)
+    }
+
+    pub const fn from_millis(millis: u32) -> Self {
+        Self(millis * 1000)
+    }
+
+    pub const fn as_micros(self) -> u32 {
+        self.0
+    }
+
+    pub const fn as_millis(self) -> u32 {
+        self.0 / 1000
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, rhs: Duration) -> Instant {
+        Instant(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        Duration(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl Mul<u32> for Duration {
+    type Output = Duration;
+
+    fn mul(self, rhs: u32) -> Duration {
+        Duration(self.0.saturating_mul(rhs))
+    }
+}
+
+impl Div<u32> for Duration {
+    type Output = Duration;
+
+    fn div(self, rhs: u32) -> Duration {
+        Duration(self.0 / rhs)
+    }
+}
+
+impl uDebug for Instant {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        <u32 as uDebug>::fmt(&self.0, f)
+    }
+}
+
+impl uDisplay for Instant {
+    #[inline(always)]
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        <Instant as uDebug>::fmt(self, f)
+    }
+}
+
+impl uDebug for Duration {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        <u32 as uDebug>::fmt(&self.0, f)
+    }
+}
+
+impl uDisplay for Duration {
+    #[inline(always)]
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        <Duration as uDebug>::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn arithmetic_wraps_around_u32_max() {
+        let before = Instant::from_micros(u32::MAX - 9);
+        let after = before + Duration::from_micros(20);
+
+        assert_eq!(after, Instant::from_micros(10));
+        assert_eq!(after - before, Duration::from_micros(20));
+        assert_eq!(after - Duration::from_micros(20), before);
+        assert!(before.is_before(after));
+        assert!(!after.is_before(before));
+        assert!(after.is_at_or_after(before));
+        assert!(after.is_at_or_after(after));
+    }
+}
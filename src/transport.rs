@@ -0,0 +1,118 @@
+use ufmt::{derive::uDebug, uDebug, uDisplay, uWrite, Formatter};
+
+/// State of the transport
+#[derive(Copy, Clone, PartialEq, Debug, uDebug)]
+pub enum TransportState {
+    /// The clock is ignored and the next step is the first one
+    Stopped,
+    /// The sequence is played
+    Running,
+    /// The clock is ignored and the sequence continues from the current step
+    Paused,
+}
+
+impl uDisplay for TransportState {
+    #[inline(always)]
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        <TransportState as uDebug>::fmt(self, f)
+    }
+}
+
+/// Command that changes the `TransportState`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TransportCommand {
+    Run,
+    Stop,
+    /// Pause while running, or continue while paused
+    Pause,
+}
+
+impl TransportCommand {
+    /// Return the command for the given serial input (`r`un, `s`top or `p`ause)
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            b'r' => Some(TransportCommand::Run),
+            b's' => Some(TransportCommand::Stop),
+            b'p' => Some(TransportCommand::Pause),
+            _ => None,
+        }
+    }
+}
+
+/// State machine for the transport (run, stop and pause)
+pub struct Transport {
+    state: TransportState,
+}
+
+impl Transport {
+    pub const fn new() -> Self {
+        Self {
+            state: TransportState::Running,
+        }
+    }
+
+    pub fn state(&self) -> TransportState {
+        self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == TransportState::Running
+    }
+
+    /// Apply the command and return the new state if it changed
+    pub fn apply(&mut self, command: TransportCommand) -> Option<TransportState> {
+        let state = match (command, self.state) {
+            (TransportCommand::Run, _) => TransportState::Running,
+            (TransportCommand::Stop, _) => TransportState::Stopped,
+            (TransportCommand::Pause, TransportState::Running) => TransportState::Paused,
+            (TransportCommand::Pause, TransportState::Paused) => TransportState::Running,
+            (TransportCommand::Pause, TransportState::Stopped) => TransportState::Stopped,
+        };
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+
+        Some(state)
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands_change_state() {
+        let mut transport = Transport::new();
+        assert!(transport.is_running());
+        assert_eq!(transport.apply(TransportCommand::Run), None);
+        assert_eq!(
+            transport.apply(TransportCommand::Pause),
+            Some(TransportState::Paused)
+        );
+        assert_eq!(
+            transport.apply(TransportCommand::Pause),
+            Some(TransportState::Running)
+        );
+        assert_eq!(
+            transport.apply(TransportCommand::Stop),
+            Some(TransportState::Stopped)
+        );
+        // Pausing a stopped transport does nothing
+        assert_eq!(transport.apply(TransportCommand::Pause), None);
+        assert_eq!(
+            TransportCommand::from_byte(b'r'),
+            Some(TransportCommand::Run)
+        );
+        assert_eq!(TransportCommand::from_byte(b'x'), None);
+    }
+}
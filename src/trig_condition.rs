@@ -0,0 +1,49 @@
+use crate::random::Random;
+use ufmt::{derive::uDebug, uDebug, uDisplay, uWrite, Formatter};
+
+/// Condition that must be met for a step to fire its trigger
+#[derive(Copy, Clone, PartialEq, uDebug)]
+pub enum TrigCondition {
+    /// Always fire
+    Always,
+    /// Fire with the given probability (0-100%)
+    Probability(u8),
+    /// Fire on the `A`th of every `B` loops (e.g. `Loop(1, 4)` fires on the first of four loops)
+    Loop(u8, u8),
+    /// Fire only during the first loop
+    FirstLoop,
+    /// Fire on every loop except the first one
+    NotFirstLoop,
+    /// Fire only while fill is active
+    Fill,
+    /// Fire only while fill is not active
+    NotFill,
+}
+
+impl TrigCondition {
+    /// Return if the condition is met in the loop `loop_counter` (starting at 0)
+    ///
+    /// Only `Probability` consumes a number from `random`
+    pub fn evaluate(&self, loop_counter: u32, fill: bool, random: &mut Random) -> bool {
+        match *self {
+            TrigCondition::Always => true,
+            TrigCondition::Probability(percent) => random.chance(percent),
+            TrigCondition::Loop(_, 0) => false,
+            TrigCondition::Loop(a, b) => loop_counter % b as u32 + 1 == a as u32,
+            TrigCondition::FirstLoop => loop_counter == 0,
+            TrigCondition::NotFirstLoop => loop_counter != 0,
+            TrigCondition::Fill => fill,
+            TrigCondition::NotFill => !fill,
+        }
+    }
+}
+
+impl uDisplay for TrigCondition {
+    #[inline(always)]
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        <TrigCondition as uDebug>::fmt(self, f)
+    }
+}
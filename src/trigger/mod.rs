@@ -1,21 +1,23 @@
 mod trigger_factory;
 use crate::clock::StepCounterType;
-use crate::millis::millis;
-use crate::scheduler::{Task, TaskId};
-use crate::sequence::Sequence;
+use crate::gate_length::GateLength;
+use crate::random::Random;
+use crate::scheduler::{Task, TaskId, TaskQueue};
+use crate::sequence::{Sequence, MAX_RATCHETS};
+use crate::time::{Duration, Instant};
 use crate::trigger_state::TriggerState;
 use crate::DELAY_TIME;
-use arduino_uno::hal::port::mode::Output;
-use arduino_uno::hal::port::portd::PD3;
-use arduino_uno::prelude::*;
+use core::str::FromStr;
+use embedded_hal::digital::v2::OutputPin;
 pub use trigger_factory::TriggerFactory;
 use void::{ResultVoidExt, Void};
 
-type TriggerOutput = PD3<Output>;
-
 const HIGH: bool = true;
 const LOW: bool = false;
 
+/// Number of scheduled tasks needed to switch all ratchet pulses of a step on and off
+const TASK_QUEUE_CAPACITY: usize = 2 * MAX_RATCHETS as usize;
+
 #[derive(Copy, Clone, PartialOrd, PartialEq)]
 #[allow(unused)]
 pub enum TriggerMode {
@@ -27,46 +29,123 @@ pub enum TriggerMode {
     Pulse,
 }
 
-pub struct Trigger {
-    output: TriggerOutput,
+impl FromStr for TriggerMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "follow" => Ok(TriggerMode::Follow),
+            "hold" => Ok(TriggerMode::Hold),
+            "pulse" => Ok(TriggerMode::Pulse),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct Trigger<OUT: OutputPin<Error = Void>> {
+    output: OUT,
     trigger_mode: TriggerMode,
     last_trigger_state: TriggerState,
-    scheduled_task: Option<Task<TriggerTask>>,
+    scheduled_tasks: TaskQueue<TriggerTask, TASK_QUEUE_CAPACITY>,
+    /// Gate length of the current step
+    gate_length: GateLength,
+    /// Number of pulses of the current step
+    ratchets: u8,
+    /// Interval of the clock if it is known in advance
+    clock_interval: Option<Duration>,
+    /// Timestamp of the last clock-trigger
+    last_rise: Option<Instant>,
+    /// Interval between the last two clock-triggers
+    period: Option<Duration>,
+    /// Number of completed loops through the sequence
+    loop_counter: u32,
+    fill: bool,
+    random: Random,
 }
 
-impl Trigger {
-    pub fn new(output: TriggerOutput, trigger_mode: TriggerMode) -> Self {
+impl<OUT: OutputPin<Error = Void>> Trigger<OUT> {
+    pub fn new(output: OUT, trigger_mode: TriggerMode, seed: u32) -> Self {
         Self {
             output,
             trigger_mode,
             last_trigger_state: TriggerState::Unchanged,
-            scheduled_task: None,
+            scheduled_tasks: TaskQueue::new(),
+            gate_length: GateLength::TriggerMode,
+            ratchets: 1,
+            clock_interval: None,
+            last_rise: None,
+            period: None,
+            loop_counter: 0,
+            fill: false,
+            random: Random::new(seed),
         }
     }
 
+    /// Handle the clock-trigger `state` for the step `step_counter`
+    ///
+    /// `wrapped` tells if the playhead wrapped around to the start of the loop with this
+    /// clock-trigger, which counts the loops for the trig conditions.
     pub fn check(
         &mut self,
+        now: Instant,
         state: TriggerState,
         step_counter: StepCounterType,
-        sequence: Sequence,
+        wrapped: bool,
+        sequence: &Sequence,
     ) {
         match state {
             TriggerState::Rise => {
-                let step_pointer: u8 = 0b00000001 << step_counter;
-                self.set_output(if sequence.matches(step_pointer) {
-                    HIGH
-                } else {
-                    LOW
-                })
-                .void_unwrap();
-
-                if self.trigger_mode == TriggerMode::Pulse {
-                    self.scheduled_task =
-                        Some(Task::new(TriggerTask::SetOff, millis() + DELAY_TIME as u32));
+                let last_rise = self.last_rise;
+                self.period = self
+                    .clock_interval
+                    .or_else(|| last_rise.map(|last_rise| now.duration_since(last_rise)));
+                self.last_rise = Some(now);
+                if wrapped {
+                    self.loop_counter = self.loop_counter.wrapping_add(1);
+                }
+
+                let fires = sequence.matches(step_counter)
+                    && sequence.get_condition(step_counter).evaluate(
+                        self.loop_counter,
+                        self.fill,
+                        &mut self.random,
+                    );
+                self.set_output(if fires { HIGH } else { LOW })
+                    .void_unwrap();
+
+                self.gate_length = sequence.get_gate_length(step_counter);
+                // Without a measured period the gate length falls back to the trigger mode
+                if self.gate_length != GateLength::Tie && self.gate_duration().is_none() {
+                    self.gate_length = GateLength::TriggerMode;
+                }
+
+                self.ratchets = match self.period {
+                    Some(_) if fires => sequence.get_ratchets(step_counter),
+                    _ => 1,
+                };
+
+                self.scheduled_tasks.clear();
+                if fires {
+                    match (self.period, self.gate_length, self.trigger_mode) {
+                        (Some(period), _, _) if self.ratchets > 1 => {
+                            self.schedule_ratchets(now, period)
+                        }
+                        (Some(period), GateLength::Percent(_), _) => {
+                            if let Some(duration) = self.gate_length.duration(period) {
+                                self.schedule(TriggerTask::SetOff, now + duration)
+                            }
+                        }
+                        (_, GateLength::TriggerMode, TriggerMode::Pulse) => {
+                            self.schedule(TriggerTask::SetOff, now + DELAY_TIME)
+                        }
+                        _ => {}
+                    }
                 }
             }
             TriggerState::Fall => {
                 match self.trigger_mode {
+                    // The step's gate length or ratchets control the output
+                    _ if self.gate_length != GateLength::TriggerMode || self.ratchets > 1 => {}
                     TriggerMode::Follow => self.set_output(LOW).void_unwrap(),
                     TriggerMode::Hold => { /* Do nothing; wait for the next external trigger */ }
                     TriggerMode::Pulse => { /* Do nothing; delay was set using Arduino library */ }
@@ -79,19 +158,66 @@ impl Trigger {
 
     pub fn check_scheduled(
         &mut self,
-        millis: u32,
+        now: Instant,
         _state: TriggerState,
         _step_counter: StepCounterType,
-        _sequence: Sequence,
+        _sequence: &Sequence,
     ) {
-        if let Some(task) = &self.scheduled_task {
-            if task.id == TriggerTask::SetOff && millis >= task.timestamp {
-                self.set_output(LOW).void_unwrap();
-                self.scheduled_task = None
+        while let Some(task) = self.scheduled_tasks.pop_due(now) {
+            match task.id {
+                TriggerTask::SetOn => self.set_output(HIGH).void_unwrap(),
+                TriggerTask::SetOff => self.set_output(LOW).void_unwrap(),
             }
         }
     }
 
+    /// Cancel the scheduled pulses and set the output low (e.g. when the transport stops)
+    ///
+    /// The next clock-trigger does not measure the period across the silence.
+    pub fn silence(&mut self) {
+        self.scheduled_tasks.clear();
+        self.last_rise = None;
+        self.set_output(LOW).void_unwrap()
+    }
+
+    /// Set the interval of the clock if it is known in advance (e.g. from `InternalClock`)
+    ///
+    /// Otherwise the interval is measured between the clock-triggers
+    pub fn set_clock_interval(&mut self, clock_interval: Option<Duration>) {
+        self.clock_interval = clock_interval
+    }
+
+    /// Activate or deactivate fill (used by `TrigCondition::Fill` and `TrigCondition::NotFill`)
+    pub fn set_fill(&mut self, fill: bool) {
+        self.fill = fill
+    }
+
+    /// Schedule `ratchets` evenly spaced pulses within `period` starting at `now`
+    fn schedule_ratchets(&mut self, now: Instant, period: Duration) {
+        let interval = period / self.ratchets as u32;
+        let pulse_width = self.gate_length.duration(interval).unwrap_or(interval / 2);
+        for i in 0..self.ratchets as u32 {
+            let start = now + interval * i;
+            if i > 0 {
+                self.schedule(TriggerTask::SetOn, start);
+            }
+            // A tied step keeps the last pulse high until the next step
+            if self.gate_length != GateLength::Tie || i + 1 < self.ratchets as u32 {
+                self.schedule(TriggerTask::SetOff, start + pulse_width);
+            }
+        }
+    }
+
+    fn schedule(&mut self, id: TriggerTask, timestamp: Instant) {
+        // The queue is large enough to hold all pulses of a step
+        let _ = self.scheduled_tasks.push(Task::new(id, timestamp));
+    }
+
+    fn gate_duration(&self) -> Option<Duration> {
+        self.period
+            .and_then(|period| self.gate_length.duration(period))
+    }
+
     fn set_output(&mut self, value: bool) -> Result<(), Void> {
         if value == HIGH {
             self.output.set_high()
@@ -103,7 +229,129 @@ impl Trigger {
 
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
 enum TriggerTask {
+    SetOn,
     SetOff,
 }
 
 impl TaskId for TriggerTask {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::MockOutputPin;
+    use crate::random::DEFAULT_SEED;
+    use crate::seq;
+    use crate::trig_condition::TrigCondition;
+
+    const SEQUENCE: Sequence = seq!(15, 15, 15, 0).with_gate_lengths(&[
+        GateLength::Percent(50),
+        GateLength::Tie,
+        GateLength::TriggerMode,
+    ]);
+
+    fn at(millis: u32) -> Instant {
+        Instant::from_millis(millis)
+    }
+
+    fn rise(trigger: &mut Trigger<MockOutputPin>, now: Instant, step_counter: StepCounterType) {
+        trigger.check_scheduled(now, TriggerState::Rise, step_counter, &SEQUENCE);
+        trigger.check(now, TriggerState::Rise, step_counter, false, &SEQUENCE);
+    }
+
+    fn run(trigger: &mut Trigger<MockOutputPin>, now: Instant, step_counter: StepCounterType) {
+        trigger.check_scheduled(now, TriggerState::Unchanged, step_counter, &SEQUENCE);
+        trigger.check(now, TriggerState::Unchanged, step_counter, false, &SEQUENCE);
+    }
+
+    #[test]
+    fn percent_gate_length_uses_measured_period() {
+        let output = MockOutputPin::new();
+        let mut trigger = Trigger::new(output.clone(), TriggerMode::Pulse, DEFAULT_SEED);
+        rise(&mut trigger, at(0), 2);
+        rise(&mut trigger, at(100), 0);
+        run(&mut trigger, at(149), 0);
+        assert!(output.is_high());
+        run(&mut trigger, at(150), 0);
+        assert!(!output.is_high());
+    }
+
+    #[test]
+    fn scheduled_off_crosses_counter_overflow() {
+        let output = MockOutputPin::new();
+        let mut trigger = Trigger::new(output.clone(), TriggerMode::Pulse, DEFAULT_SEED);
+        rise(&mut trigger, Instant::from_micros(u32::MAX - 119_999), 2);
+        rise(&mut trigger, Instant::from_micros(u32::MAX - 19_999), 0);
+        run(&mut trigger, Instant::from_micros(u32::MAX), 0);
+        run(&mut trigger, Instant::from_micros(29_999), 0);
+        assert!(output.is_high());
+        run(&mut trigger, Instant::from_micros(30_000), 0);
+        assert!(!output.is_high());
+    }
+
+    #[test]
+    fn tied_step_holds_until_next_step() {
+        let output = MockOutputPin::new();
+        let mut trigger = Trigger::new(output.clone(), TriggerMode::Follow, DEFAULT_SEED);
+        rise(&mut trigger, at(0), 0);
+        rise(&mut trigger, at(100), 1);
+        trigger.check(at(150), TriggerState::Fall, 1, false, &SEQUENCE);
+        run(&mut trigger, at(199), 1);
+        assert!(output.is_high());
+        rise(&mut trigger, at(200), 2);
+        assert!(output.is_high());
+        trigger.check(at(250), TriggerState::Fall, 2, false, &SEQUENCE);
+        assert!(!output.is_high());
+    }
+
+    #[test]
+    fn ratchets_fire_evenly_spaced_pulses() {
+        const RATCHETS: Sequence = seq!(15, 15).with_ratchets(&[3]);
+        let output = MockOutputPin::new();
+        let mut trigger = Trigger::new(output.clone(), TriggerMode::Pulse, DEFAULT_SEED);
+        trigger.set_clock_interval(Some(Duration::from_millis(90)));
+        let mut edges = vec![];
+        for millis in 0..90 {
+            let state = if millis == 0 {
+                TriggerState::Rise
+            } else {
+                TriggerState::Unchanged
+            };
+            let was_high = output.is_high();
+            trigger.check_scheduled(at(millis), state, 0, &RATCHETS);
+            trigger.check(at(millis), state, 0, false, &RATCHETS);
+            if output.is_high() != was_high {
+                edges.push(millis);
+            }
+        }
+
+        assert_eq!(edges, [0, 15, 30, 45, 60, 75]);
+    }
+
+    #[test]
+    fn conditions_are_evaluated_per_loop() {
+        const CONDITIONAL: Sequence =
+            seq!(15, 15).with_conditions(&[TrigCondition::Loop(2, 3), TrigCondition::NotFill]);
+        let output = MockOutputPin::new();
+        let mut trigger = Trigger::new(output.clone(), TriggerMode::Follow, DEFAULT_SEED);
+        let mut fired = vec![];
+        for loop_counter in 0..6 {
+            trigger.set_fill(loop_counter == 4);
+            for step_counter in 0..2 {
+                let wrapped = loop_counter > 0 && step_counter == 0;
+                trigger.check(
+                    at(0),
+                    TriggerState::Rise,
+                    step_counter,
+                    wrapped,
+                    &CONDITIONAL,
+                );
+                fired.push(output.is_high());
+            }
+        }
+
+        assert_eq!(
+            fired,
+            [false, true, true, true, false, true, false, true, true, false, false, true]
+        );
+    }
+}
@@ -1,18 +1,45 @@
+use crate::random::DEFAULT_SEED;
 use crate::trigger::{Trigger, TriggerMode};
-use arduino_uno::hal::port::mode::Output;
-use arduino_uno::hal::port::portd::PD3;
+use embedded_hal::digital::v2::OutputPin;
+use void::Void;
 
-pub struct TriggerFactory {}
+pub struct TriggerFactory {
+    trigger_mode: TriggerMode,
+    seed: Option<u32>,
+}
 
 impl TriggerFactory {
     pub fn new() -> Self {
-        Self {}
-    }
-
-    pub fn build(&self, trigger_out: PD3<Output>) -> Trigger {
         // let trigger_mode: TriggerMode = TriggerMode::Hold;
         // let trigger_mode: TriggerMode = TriggerMode::Follow;
         let trigger_mode: TriggerMode = TriggerMode::Pulse;
-        Trigger::new(trigger_out, trigger_mode)
+        Self::with_trigger_mode(trigger_mode)
+    }
+
+    pub fn with_trigger_mode(trigger_mode: TriggerMode) -> Self {
+        Self {
+            trigger_mode,
+            seed: None,
+        }
+    }
+
+    /// Use the given seed for the trigger's random numbers (e.g. a stored seed)
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Use the given seed only if no seed was configured via `with_seed()`
+    pub fn with_fallback_seed(mut self, seed: u32) -> Self {
+        self.seed = self.seed.or(Some(seed));
+        self
+    }
+
+    pub fn build<OUT: OutputPin<Error = Void>>(&self, trigger_out: OUT) -> Trigger<OUT> {
+        Trigger::new(
+            trigger_out,
+            self.trigger_mode,
+            self.seed.unwrap_or(DEFAULT_SEED),
+        )
     }
 }
@@ -0,0 +1,161 @@
+use crate::dac_byte::DacByte;
+use crate::random::Random;
+use crate::sequence::Sequence;
+use crate::tempo_pot::{PotFilter, ADC_MAX};
+
+/// Generative mode that slowly mutates the current sequence like a Turing machine
+///
+/// The steps of the sequence form a shift register that rotates by one step with every played
+/// step, so a loop of the sequence is a full rotation. The played step wraps around to the end of
+/// the register, and with a chance of `probability` percent its value is flipped or replaced by a
+/// random one on the way. A probability of `0` locks the sequence, `100` changes every step.
+///
+/// The register is read at the position of the playhead, so the rotation leaves the values where
+/// they are and only the played step changes. Gate lengths, trig conditions and ratchets stay with
+/// their steps.
+pub struct TuringMachine {
+    /// Mutated copy of the selected sequence (loaded on the first use)
+    register: Option<Sequence>,
+    probability: u8,
+    random: Random,
+    pot: PotFilter,
+}
+
+/// Mixed into the seed so the register does not mutate in step with other users of the same seed
+const SEED_MASK: u32 = 0x7e57_c0de;
+
+impl TuringMachine {
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            register: None,
+            probability: 0,
+            random: Random::new(seed ^ SEED_MASK),
+            pot: PotFilter::new(),
+        }
+    }
+
+    /// Return the chance in percent that a step is changed when it wraps around
+    pub fn probability(&self) -> u8 {
+        self.probability
+    }
+
+    pub fn set_probability(&mut self, probability: u8) {
+        self.probability = probability.min(100)
+    }
+
+    /// Set the probability from a reading of the pot and return it if it changed
+    ///
+    /// The lowest part of the pot (about 1%) locks the sequence.
+    pub fn update_pot(&mut self, reading: u16) -> Option<u8> {
+        let position = self.pot.update(reading)?;
+        let probability = (position as u32 * 100 / ADC_MAX as u32) as u8;
+        if probability == self.probability {
+            return None;
+        }
+        self.probability = probability;
+
+        Some(probability)
+    }
+
+    /// Start over with a fresh copy of `sequence`
+    pub fn load(&mut self, sequence: &Sequence) {
+        self.register = Some(*sequence)
+    }
+
+    /// Return the current state of the register (a copy of `sequence` if nothing is loaded yet)
+    pub fn sequence(&mut self, sequence: &Sequence) -> &Sequence {
+        self.register.get_or_insert(*sequence)
+    }
+
+    /// Wrap the played step around to the end of the register and mutate it by chance
+    pub fn shift(&mut self, step: usize) {
+        let register = match self.register.as_mut() {
+            Some(register) => register,
+            None => return,
+        };
+        let value = match register.get_step(step) {
+            Some(value) => value.value(),
+            None => return,
+        };
+        if !self.random.chance(self.probability) {
+            return;
+        }
+
+        let max = DacByte::max().value();
+        let value = if self.random.chance(50) {
+            max - value
+        } else {
+            (self.random.next_u32() % (max as u32 + 1)) as u8
+        };
+        register.set_step(step, DacByte::new(value));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gate_length::GateLength;
+    use crate::random::DEFAULT_SEED;
+    use crate::seq;
+
+    fn values(sequence: &Sequence) -> Vec<u8> {
+        (0..sequence.len())
+            .filter_map(|i| sequence.get_step(i))
+            .map(|step| step.value())
+            .collect()
+    }
+
+    /// Play one loop of the register and return the values that were played
+    fn play_loop(turing_machine: &mut TuringMachine, sequence: &Sequence) -> Vec<u8> {
+        (0..sequence.len())
+            .map(|step| {
+                let value = turing_machine.sequence(sequence).get_step(step).unwrap();
+                turing_machine.shift(step);
+                value.value()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn repeats_the_loop_when_locked() {
+        let sequence = seq!(1, 3, 5, 8, 9, 10, 12, 15);
+        let mut turing_machine = TuringMachine::new(DEFAULT_SEED);
+        turing_machine.set_probability(100);
+        play_loop(&mut turing_machine, &sequence);
+
+        turing_machine.set_probability(0);
+        let first_loop = play_loop(&mut turing_machine, &sequence);
+        let second_loop = play_loop(&mut turing_machine, &sequence);
+        assert_eq!(first_loop, second_loop);
+        assert_ne!(first_loop, values(&sequence));
+    }
+
+    #[test]
+    fn mutates_the_played_steps() {
+        static GATE_LENGTHS: [GateLength; 2] = [GateLength::Tie, GateLength::Percent(50)];
+        let sequence = seq!(1, 3, 5, 8, 9, 10, 12, 15).with_gate_lengths(&GATE_LENGTHS);
+        let mut turing_machine = TuringMachine::new(DEFAULT_SEED);
+        turing_machine.set_probability(100);
+        for _ in 0..10 {
+            play_loop(&mut turing_machine, &sequence);
+        }
+        let register = *turing_machine.sequence(&sequence);
+        assert_ne!(values(&register), values(&sequence));
+        assert!((0..sequence.len())
+            .all(|step| register.get_gate_length(step) == sequence.get_gate_length(step)));
+
+        turing_machine.load(&sequence);
+        assert_eq!(
+            values(turing_machine.sequence(&sequence)),
+            values(&sequence)
+        );
+        assert_eq!(turing_machine.update_pot(0), Some(0));
+    }
+
+    #[test]
+    fn seeds_differently_from_the_trigger() {
+        let mut trigger_random = Random::new(DEFAULT_SEED);
+        let mut turing_machine = TuringMachine::new(DEFAULT_SEED);
+        assert_ne!(trigger_random.next_u32(), turing_machine.random.next_u32());
+    }
+}
@@ -0,0 +1,173 @@
+//! Record the simulated signals as Value Change Dump (e.g. to inspect them in GTKWave)
+use crate::simulator::Snapshot;
+use crate::STEP_LED_COUNT;
+use std::io::{self, Write};
+
+/// Number of recorded signals: clock input, trigger output, step outputs, sequence change
+/// output, four DAC bits and the combined DAC value
+const SIGNAL_COUNT: usize = 4 + STEP_LED_COUNT + 4;
+
+struct Signal {
+    name: String,
+    width: u8,
+}
+
+pub struct VcdRecorder<W: Write> {
+    writer: W,
+    signals: Vec<Signal>,
+    last_values: Option<[u8; SIGNAL_COUNT]>,
+}
+
+impl<W: Write> VcdRecorder<W> {
+    /// Create a new recorder and write the VCD header
+    pub fn new(writer: W) -> io::Result<Self> {
+        let mut signals = vec![Signal::bit("clock_in"), Signal::bit("trigger_out")];
+        for i in 1..=STEP_LED_COUNT {
+            signals.push(Signal::bit(&format!("step_out_{}", i)));
+        }
+        signals.push(Signal::bit("sequence_change_out"));
+        for i in 0..4 {
+            signals.push(Signal::bit(&format!("dac_a{}", i)));
+        }
+        signals.push(Signal {
+            name: "cv".to_owned(),
+            width: 4,
+        });
+
+        let mut recorder = Self {
+            writer,
+            signals,
+            last_values: None,
+        };
+        recorder.write_header()?;
+
+        Ok(recorder)
+    }
+
+    /// Write the values of all signals that changed since the last recorded snapshot
+    pub fn record(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let values = Self::values(snapshot);
+        let last_values = self.last_values;
+        let changed: Vec<usize> = (0..SIGNAL_COUNT)
+            .filter(|i| match last_values {
+                Some(last) => last[*i] != values[*i],
+                None => true,
+            })
+            .collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(self.writer, "#{}", snapshot.millis)?;
+        if last_values.is_none() {
+            writeln!(self.writer, "$dumpvars")?;
+        }
+        for i in changed {
+            let signal = &self.signals[i];
+            if signal.width == 1 {
+                writeln!(self.writer, "{}{}", values[i], identifier(i))?;
+            } else {
+                writeln!(
+                    self.writer,
+                    "b{:0width$b} {}",
+                    values[i],
+                    identifier(i),
+                    width = signal.width as usize
+                )?;
+            }
+        }
+        if last_values.is_none() {
+            writeln!(self.writer, "$end")?;
+        }
+        self.last_values = Some(values);
+
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        writeln!(self.writer, "$version twostep simulator $end")?;
+        writeln!(self.writer, "$timescale 1ms $end")?;
+        writeln!(self.writer, "$scope module twostep $end")?;
+        for (i, signal) in self.signals.iter().enumerate() {
+            writeln!(
+                self.writer,
+                "$var wire {} {} {} $end",
+                signal.width,
+                identifier(i),
+                signal.name
+            )?;
+        }
+        writeln!(self.writer, "$upscope $end")?;
+        writeln!(self.writer, "$enddefinitions $end")
+    }
+
+    fn values(snapshot: &Snapshot) -> [u8; SIGNAL_COUNT] {
+        let mut values = [0; SIGNAL_COUNT];
+        values[0] = snapshot.clock_input as u8;
+        values[1] = snapshot.trigger_output as u8;
+        for (i, step_output) in snapshot.step_outputs.iter().enumerate() {
+            values[2 + i] = *step_output as u8;
+        }
+        values[2 + STEP_LED_COUNT] = snapshot.sequence_change_output as u8;
+        for bit in 0..4 {
+            values[3 + STEP_LED_COUNT + bit] = (snapshot.dac >> bit) & 1;
+        }
+        values[SIGNAL_COUNT - 1] = snapshot.dac;
+
+        values
+    }
+}
+
+impl Signal {
+    fn bit(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            width: 1,
+        }
+    }
+}
+
+/// Return the short VCD identifier code for the signal at `index`
+fn identifier(index: usize) -> char {
+    (b'!' + index as u8) as char
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RGB_LED_COUNT;
+    use smart_leds::RGB8;
+
+    fn snapshot(millis: u32, trigger_output: bool, dac: u8) -> Snapshot {
+        Snapshot {
+            millis,
+            clock_input: false,
+            trigger_output,
+            step_outputs: [false; STEP_LED_COUNT],
+            sequence_change_output: false,
+            dac,
+            leds: [RGB8::default(); RGB_LED_COUNT],
+        }
+    }
+
+    #[test]
+    fn record_writes_only_changes() {
+        let mut recorder = VcdRecorder::new(Vec::new()).unwrap();
+        recorder.record(&snapshot(0, false, 0)).unwrap();
+        recorder.record(&snapshot(1, false, 0)).unwrap();
+        recorder.record(&snapshot(250, true, 5)).unwrap();
+        recorder.record(&snapshot(255, false, 5)).unwrap();
+        let output = String::from_utf8(recorder.into_inner().unwrap()).unwrap();
+
+        assert!(output.contains("$var wire 1 \" trigger_out $end"));
+        assert!(output.contains("$var wire 4 - cv $end"));
+        assert!(!output.contains("#1\n"));
+        assert!(output.ends_with("#250\n1\"\n1)\n1+\nb0101 -\n#255\n0\"\n"));
+    }
+}
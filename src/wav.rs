@@ -0,0 +1,148 @@
+//! Render the simulated CV and trigger outputs as a two-channel WAV file
+use crate::dac::{ladder_millivolts, HIGH_LEVEL_MILLIVOLTS};
+use crate::dac_byte::DacByte;
+use crate::simulator::Snapshot;
+use std::io::{self, Write};
+
+/// Collects the samples of the resistor ladder voltage (left channel) and the trigger output
+/// (right channel)
+///
+/// Full scale of a channel corresponds to `HIGH_LEVEL_MILLIVOLTS`.
+pub struct WavRenderer {
+    sample_rate: u32,
+    samples: Vec<[i16; 2]>,
+    last_snapshot: Option<Snapshot>,
+}
+
+impl WavRenderer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            samples: vec![],
+            last_snapshot: None,
+        }
+    }
+
+    /// Hold the values of the previous snapshot until the time of `snapshot`
+    pub fn record(&mut self, snapshot: &Snapshot) {
+        if let Some(last_snapshot) = self.last_snapshot {
+            let sample = [
+                sample_for_millivolts(ladder_millivolts(DacByte::new(last_snapshot.dac))),
+                if last_snapshot.trigger_output {
+                    i16::MAX
+                } else {
+                    0
+                },
+            ];
+            let end = snapshot.millis as u64 * self.sample_rate as u64 / 1000;
+            while (self.samples.len() as u64) < end {
+                self.samples.push(sample);
+            }
+        } else {
+            // Start the recording at the first snapshot
+            self.samples.clear();
+        }
+        self.last_snapshot = Some(*snapshot);
+    }
+
+    pub fn samples(&self) -> &[[i16; 2]] {
+        &self.samples
+    }
+
+    /// Write the samples as 16 bit PCM WAV file
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        const CHANNELS: u16 = 2;
+        const BITS_PER_SAMPLE: u16 = 16;
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+        let data_length = self.samples.len() as u32 * block_align as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_length).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&CHANNELS.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        writer.write_all(&(self.sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_length.to_le_bytes())?;
+        for sample in &self.samples {
+            writer.write_all(&sample[0].to_le_bytes())?;
+            writer.write_all(&sample[1].to_le_bytes())?;
+        }
+        writer.flush()
+    }
+}
+
+fn sample_for_millivolts(millivolts: u32) -> i16 {
+    (millivolts * i16::MAX as u32 / HIGH_LEVEL_MILLIVOLTS) as i16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{RGB_LED_COUNT, STEP_LED_COUNT};
+    use smart_leds::RGB8;
+
+    fn snapshot(millis: u32, trigger_output: bool, dac: u8) -> Snapshot {
+        Snapshot {
+            millis,
+            clock_input: false,
+            trigger_output,
+            step_outputs: [false; STEP_LED_COUNT],
+            sequence_change_output: false,
+            dac,
+            leds: [RGB8::default(); RGB_LED_COUNT],
+        }
+    }
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    }
+
+    #[test]
+    fn writes_held_cv_and_trigger_samples() {
+        let mut renderer = WavRenderer::new(2000);
+        renderer.record(&snapshot(0, true, 8));
+        renderer.record(&snapshot(1, false, 0));
+        renderer.record(&snapshot(2, false, 0));
+
+        // Half of the full scale for the CV while the trigger is high, then silence
+        let half = (i16::MAX as u32 / 2) as i16;
+        assert_eq!(
+            renderer.samples(),
+            [[half, i16::MAX], [half, i16::MAX], [0, 0], [0, 0]]
+        );
+
+        let mut data = Vec::new();
+        renderer.write(&mut data).unwrap();
+        assert_eq!(data.len(), 44 + 4 * 4);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(u32_at(&data, 4), 36 + 4 * 4);
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(&data, 20), 1);
+        assert_eq!(u16_at(&data, 22), 2);
+        assert_eq!(u32_at(&data, 24), 2000);
+        assert_eq!(u32_at(&data, 28), 2000 * 4);
+        assert_eq!(u16_at(&data, 32), 4);
+        assert_eq!(u16_at(&data, 34), 16);
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(u32_at(&data, 40), 4 * 4);
+        assert_eq!(u16_at(&data, 44) as i16, half);
+        assert_eq!(u16_at(&data, 46) as i16, i16::MAX);
+    }
+}